@@ -0,0 +1,4 @@
+//! The C library `libqlox`, which is built apart from `qlox`, so that only the users of the C API build it.
+//! It exports the C functions defined by the `ffi` feature of `qlox`, which are linked in from it.
+
+extern crate qlox;
@@ -0,0 +1,45 @@
+#include <stdio.h>
+#include <string.h>
+#include "qlox.h"
+
+static int32_t add(void *user_data, const QloxValue *args, size_t arg_count, QloxValue *result) {
+  int *calls = user_data;
+  ++*calls;
+  for (size_t i = 0; i < arg_count; ++i) {
+    if (args[i].kind != QLOX_VALUE_KIND_NUMBER) {
+      result->kind = QLOX_VALUE_KIND_STRING;
+      result->string = "expected numbers";
+      return 1;
+    }
+  }
+  result->kind = QLOX_VALUE_KIND_NUMBER;
+  result->number = args[0].number + args[1].number;
+  return 0;
+}
+
+#define CHECK(cond)                                              \
+  do {                                                           \
+    if (!(cond)) {                                               \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+      return 1;                                                  \
+    }                                                            \
+  } while (0)
+
+int main(void) {
+  int calls = 0;
+  QloxInterpreter *interpreter = qlox_new();
+  CHECK(qlox_define_native(interpreter, "add", 2, add, &calls) == QLOX_OK);
+
+  CHECK(qlox_eval(interpreter, "print add(1, 2);") == QLOX_OK);
+  CHECK(strcmp(qlox_diagnostics(interpreter), "") == 0);
+
+  CHECK(qlox_eval(interpreter, "add(1, \"2\");") == QLOX_RUNTIME_ERR);
+  CHECK(strstr(qlox_diagnostics(interpreter), "native function `add` failed: expected numbers") != NULL);
+  CHECK(qlox_eval(interpreter, "print @;") == QLOX_SCANNER_ERR);
+  CHECK(qlox_eval(interpreter, "print;") == QLOX_PARSER_ERR);
+  CHECK(qlox_eval(interpreter, NULL) == QLOX_USAGE_ERR);
+  CHECK(calls == 2);
+
+  qlox_free(interpreter);
+  return 0;
+}
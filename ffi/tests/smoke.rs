@@ -0,0 +1,28 @@
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+/// Compiles `tests/c/smoke.c` against the C library with the system `cc`, and runs it.
+#[test]
+fn c_smoke_test() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // The C library is built next to the test, in `target/debug/deps`.
+    let exe = env::current_exe().unwrap();
+    let lib_dir = exe.parent().unwrap();
+    let smoke = Path::new(env!("CARGO_TARGET_TMPDIR")).join("smoke");
+
+    let status = Command::new("cc")
+        .arg(root.join("tests/c/smoke.c"))
+        .arg("-I").arg(root.join("../include"))
+        .arg("-L").arg(lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lqlox")
+        .arg("-o").arg(&smoke)
+        .status()
+        .expect("`cc` must be installed");
+    assert!(status.success());
+
+    let output = Command::new(&smoke).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3\n");
+}
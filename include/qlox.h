@@ -0,0 +1,103 @@
+#ifndef QLOX_H
+#define QLOX_H
+
+/* Declares the C API of src/ffi.rs, generated by `cbindgen --config cbindgen.toml`. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Success.
+#define QLOX_OK 0
+
+// An unexpected failure, such as a panic, which is kept from unwinding into C.
+#define QLOX_GENERIC_ERR 1
+
+// A null or non-UTF-8 argument.
+#define QLOX_USAGE_ERR 2
+
+#define QLOX_IO_ERR 3
+
+#define QLOX_SCANNER_ERR 50
+
+#define QLOX_PARSER_ERR 51
+
+#define QLOX_RESOLVER_ERR 52
+
+#define QLOX_RUNTIME_ERR 53
+
+#define QLOX_COMPILER_ERR 54
+
+#define QLOX_LOXC_ERR 55
+
+// The type of a `QloxValue`.
+typedef enum QloxValueKind {
+  QLOX_VALUE_KIND_NIL,
+  QLOX_VALUE_KIND_BOOL,
+  QLOX_VALUE_KIND_NUMBER,
+  QLOX_VALUE_KIND_STRING,
+  // A function, a class or an instance, which is passed to C as the string it's printed as.
+  QLOX_VALUE_KIND_OBJECT,
+} QloxValueKind;
+
+// An interpreter owned by C code, which keeps the diagnostics of its last run.
+typedef struct QloxInterpreter QloxInterpreter;
+
+// A Lox value passed between C and Lox, whose fields other than `kind` are read only for the kinds they're for.
+typedef struct QloxValue {
+  QloxValueKind kind;
+  bool boolean;
+  double number;
+  // A NUL-terminated UTF-8 string for `String` and `Object`.
+  const char *string;
+} QloxValue;
+
+// A C function called as a native Lox function with `arg_count` arguments in `args`, which are valid only
+// during the call. It writes its return value into `result`, and returns 0 on success. On failure, it returns
+// any other value, and may write a `String` value into `result` as the message of the runtime error.
+// A string written into `result` is copied as soon as the function returns.
+typedef int32_t (*QloxNativeFn)(void *user_data,
+                                const QloxValue *args,
+                                size_t arg_count,
+                                QloxValue *result);
+
+// Creates an interpreter, which must be freed by `qlox_free`, or returns null if it fails.
+QloxInterpreter *qlox_new(void);
+
+// Frees an interpreter created by `qlox_new`.
+//
+// # Safety
+// `interpreter` must be null, or created by `qlox_new` and not freed yet.
+void qlox_free(QloxInterpreter *interpreter);
+
+// Runs the NUL-terminated UTF-8 `source`, and returns the exit code `qlox` would exit with for it,
+// which is `QLOX_OK` on success, or `QLOX_GENERIC_ERR` if the interpreter panics.
+// The diagnostics of a failure are kept until the next run.
+//
+// # Safety
+// `interpreter` must be created by `qlox_new`, and `source` must be null or a NUL-terminated string.
+int32_t qlox_eval(QloxInterpreter *interpreter, const char *source);
+
+// Returns the diagnostics of the last run as a NUL-terminated string, which is empty if it succeeded.
+// The string is owned by `interpreter`, and valid until its next run.
+//
+// # Safety
+// `interpreter` must be created by `qlox_new`.
+const char *qlox_diagnostics(const QloxInterpreter *interpreter);
+
+// Defines the global native function `name`, which calls `function` with `user_data` and `arity` arguments.
+// Returns `QLOX_OK` on success, `QLOX_USAGE_ERR` if `name` is null or not valid UTF-8,
+// or `QLOX_GENERIC_ERR` if the interpreter panics.
+//
+// # Safety
+// `interpreter` must be created by `qlox_new`, and `name` must be null or a NUL-terminated string.
+// `user_data` must be valid for `function` as long as `interpreter` is alive.
+int32_t qlox_define_native(QloxInterpreter *interpreter,
+                           const char *name,
+                           size_t arity,
+                           QloxNativeFn function,
+                           void *user_data);
+
+#endif  /* QLOX_H */
@@ -0,0 +1,6 @@
+fn main() {
+    // Lets the frontend of `qlox` compiled into this crate tell it's compiled for the macros.
+    // `cfg(proc_macro)` can't be used instead, since it isn't set when this crate is compiled for tests.
+    println!("cargo::rustc-check-cfg=cfg(qlox_macros)");
+    println!("cargo::rustc-cfg=qlox_macros");
+}
@@ -1,8 +1,43 @@
 extern crate proc_macro;
 
-use proc_macro::TokenStream;
+// The frontend of `qlox` is compiled into this crate, too, to check Lox code at compile time.
+#[allow(dead_code)]
+#[path = "../../src/consts.rs"]
+mod consts;
+#[allow(dead_code)]
+#[path = "../../src/types.rs"]
+mod types;
+#[allow(dead_code)]
+#[path = "../../src/utils/mod.rs"]
+mod utils;
+#[allow(dead_code)]
+#[path = "../../src/src.rs"]
+mod src;
+#[allow(dead_code)]
+#[path = "../../src/token.rs"]
+mod token;
+#[allow(dead_code)]
+#[path = "../../src/scanner.rs"]
+mod scanner;
+#[allow(dead_code)]
+#[path = "../../src/ast.rs"]
+mod ast;
+#[allow(dead_code)]
+#[path = "../../src/parser.rs"]
+mod parser;
+#[allow(dead_code)]
+#[path = "../../src/resolver.rs"]
+mod resolver;
+mod program;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use proc_macro::{Span, TokenStream};
 use quote::quote;
-use syn::{parse_macro_input, Data, DeriveInput};
+use syn::{parse_macro_input, Data, DataStruct, DeriveInput, Error, Field, Fields, ImplItem, ItemImpl, LitStr};
+use syn::ext::IdentExt;
+use crate::program::TokenSource;
+use crate::src::Position;
 
 #[proc_macro_derive(ResolveSnippet)]
 pub fn derive_resolve_snippet(input: TokenStream) -> TokenStream {
@@ -12,10 +47,10 @@ pub fn derive_resolve_snippet(input: TokenStream) -> TokenStream {
         let variants = data.variants.iter().map(|v| &v.ident);
         quote! {
             impl crate::src::ResolveSnippet for #typ {
-                fn resolve_snippet(&mut self, source: &[u8]) {
+                fn resolve_snippet(&mut self, sources: &crate::src::SourceMap) {
                     match self {
                         #(
-                            Self::#variants { snippet, .. } => snippet.resolve(source)
+                            Self::#variants { snippet, .. } => snippet.resolve(sources)
                         ),*
                     }
                 }
@@ -27,4 +62,226 @@ pub fn derive_resolve_snippet(input: TokenStream) -> TokenStream {
         }
     };
     expanded.into()
-}
\ No newline at end of file
+}
+
+/// Implements `qlox::LoxClass` for a struct with named fields, which are exposed as properties of
+/// its instances, except those marked with `#[lox(skip)]`, which are created by `Default` instead.
+#[proc_macro_derive(LoxClass, attributes(lox))]
+pub fn derive_lox_class(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let typ = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(DataStruct { fields: Fields::Named(fields), .. }) if input.generics.params.is_empty() => {
+            &fields.named
+        },
+        _ => {
+            return Error::new_spanned(
+                typ,
+                "derive macro `LoxClass` can only be applied to `struct`s with named fields and without generics",
+            ).to_compile_error().into();
+        },
+    };
+
+    let mut exposed = Vec::new();
+    let mut skipped = Vec::new();
+    for field in fields {
+        match is_skipped(field) {
+            Ok(true) => skipped.push(&field.ident),
+            Ok(false) => exposed.push(&field.ident),
+            Err(e) => return e.to_compile_error().into(),
+        }
+    }
+
+    let name = typ.unraw().to_string();
+    let arity = exposed.len();
+    let indices = 0..arity;
+    let names = exposed.iter().map(|f| f.as_ref().unwrap().unraw().to_string()).collect::<Vec<_>>();
+    let expanded = quote! {
+        impl ::qlox::LoxClass for #typ {
+            const NAME: &'static str = #name;
+            const ARITY: usize = #arity;
+
+            #[allow(unused_variables)]
+            fn construct(
+                arguments: &[::qlox::Value],
+            ) -> ::std::result::Result<Self, ::qlox::ArgumentError> {
+                ::std::result::Result::Ok(#typ {
+                    #( #exposed: ::qlox::__private::argument(arguments, #indices)?, )*
+                    #( #skipped: ::std::default::Default::default(), )*
+                })
+            }
+
+            fn get(&self, name: &str) -> ::std::option::Option<::qlox::Value> {
+                match name {
+                    #(
+                        #names => ::std::option::Option::Some(
+                            ::qlox::IntoValue::into_value(::std::clone::Clone::clone(&self.#exposed)),
+                        ),
+                    )*
+                    _ => ::std::option::Option::None,
+                }
+            }
+
+            #[allow(unused_variables)]
+            fn set(
+                &mut self,
+                name: &str,
+                value: &::qlox::Value,
+            ) -> ::std::option::Option<::std::result::Result<(), ::qlox::ConversionError>> {
+                match name {
+                    #(
+                        #names => ::std::option::Option::Some(
+                            ::qlox::FromValue::from_value(value).map(|value| self.#exposed = value),
+                        ),
+                    )*
+                    _ => ::std::option::Option::None,
+                }
+            }
+
+            fn methods() -> &'static [::qlox::LoxMethod<Self>] {
+                use ::qlox::__private::{HasMethods as _, NoMethods as _};
+                (&::qlox::__private::Methods::<Self>::new()).methods()
+            }
+        }
+
+        impl ::qlox::IntoValue for #typ {
+            fn into_value(self) -> ::qlox::Value {
+                ::qlox::__private::instance(self)
+            }
+        }
+    };
+    expanded.into()
+}
+
+fn is_skipped(field: &Field) -> syn::Result<bool> {
+    let mut skipped = false;
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("lox")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skipped = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported `lox` attribute, expected `skip`"))
+            }
+        })?;
+    }
+    Ok(skipped)
+}
+
+/// Exposes the methods of an impl block taking `&self` or `&mut self` as methods of a `LoxClass`,
+/// whose arguments and return values are converted by `qlox::FromValue` and `qlox::IntoValue`.
+/// Associated functions without a receiver are left out.
+#[proc_macro_attribute]
+pub fn lox_methods(_: TokenStream, item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as ItemImpl);
+    match expand_lox_methods(&input) {
+        Ok(methods) => quote! { #input #methods }.into(),
+        Err(e) => {
+            let error = e.to_compile_error();
+            quote! { #input #error }.into()
+        },
+    }
+}
+
+fn expand_lox_methods(input: &ItemImpl) -> syn::Result<proc_macro2::TokenStream> {
+    if let Some((_, path, _)) = &input.trait_ {
+        return Err(Error::new_spanned(path, "attribute macro `lox_methods` can't be applied to trait impls"));
+    }
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(&input.generics, "attribute macro `lox_methods` doesn't support generics"));
+    }
+
+    let typ = &input.self_ty;
+    let mut methods = Vec::new();
+    for item in &input.items {
+        let ImplItem::Fn(method) = item else { continue };
+        let Some(receiver) = method.sig.receiver() else { continue };
+        if receiver.reference.is_none() {
+            return Err(Error::new_spanned(receiver, "methods of a `LoxClass` must take `self` by reference"));
+        }
+
+        let ident = &method.sig.ident;
+        let name = ident.unraw().to_string();
+        let arity = method.sig.inputs.len() - 1;
+        let indices = 0..arity;
+        methods.push(quote! {
+            ::qlox::LoxMethod {
+                name: #name,
+                arity: #arity,
+                call: |this, arguments| ::std::result::Result::Ok(::qlox::IntoValue::into_value(
+                    <#typ>::#ident(this, #( ::qlox::__private::argument(arguments, #indices)? ),*),
+                )),
+            }
+        });
+    }
+
+    Ok(quote! {
+        impl ::qlox::__private::HasMethods<#typ> for ::qlox::__private::Methods<#typ> {
+            fn methods(&self) -> &'static [::qlox::LoxMethod<#typ>] {
+                const METHODS: &[::qlox::LoxMethod<#typ>] = &[#( #methods ),*];
+                METHODS
+            }
+        }
+    })
+}
+
+/// Checks Lox code written as Rust tokens at compile time, and expands to a `qlox::Program` of it,
+/// which runs without being scanned, parsed and resolved again. Each error in the code fails the compilation
+/// at the token it's found in. Strings must be double-quoted, since Rust tokens can't be single-quoted.
+#[proc_macro]
+pub fn lox(input: TokenStream) -> TokenStream {
+    let source = TokenSource::new(input);
+    match program::parse(source.text.as_bytes()) {
+        Ok(stmts) => {
+            let call_site = Span::call_site();
+            let name = format!("<lox:{}:{}>", call_site.file(), call_site.line());
+            program::program(&name, &source.text, &stmts).into()
+        },
+        Err(errors) => {
+            compile_errors(errors.into_iter().map(|e| Error::new(source.span(e.offset).into(), e.message)))
+        },
+    }
+}
+
+/// Checks the Lox file at a path relative to the current file at compile time, like `include_str!`,
+/// and expands to a `qlox::Program` of it. Each error in the file fails the compilation with its position.
+#[proc_macro]
+pub fn include_lox(input: TokenStream) -> TokenStream {
+    let path = parse_macro_input!(input as LitStr);
+    let full_path = match Span::call_site().local_file().as_ref().and_then(|f| f.parent()) {
+        Some(dir) => dir.join(path.value()),
+        None => PathBuf::from(path.value()),
+    };
+    let source = match fs::read(&full_path) {
+        Ok(source) => source,
+        Err(e) => {
+            return Error::new(path.span(), format!("couldn't read `{}`: {e}", full_path.display()))
+                .to_compile_error()
+                .into();
+        },
+    };
+
+    match program::parse(&source) {
+        Ok(stmts) => {
+            // Names the program by the path relative to the current file's, as `file!` names the current file.
+            let name = Path::new(&Span::call_site().file()).with_file_name(path.value());
+            let program = program::program(&name.to_string_lossy(), &String::from_utf8_lossy(&source), &stmts);
+            let full_path = full_path.canonicalize().unwrap_or(full_path);
+            let full_path = full_path.to_string_lossy();
+            // Includes the file as bytes as well, so that it's compiled again whenever the file changes.
+            quote!({
+                const _: &[u8] = ::std::include_bytes!(#full_path);
+                #program
+            }).into()
+        },
+        Err(errors) => compile_errors(errors.into_iter().map(|e| {
+            let pos = Position::resolve(&source, e.offset);
+            Error::new(path.span(), format!("{} at {}:{}:{}", e.message, path.value(), pos.line + 1, pos.column + 1))
+        })),
+    }
+}
+
+fn compile_errors(errors: impl Iterator<Item = Error>) -> TokenStream {
+    let errors = errors.map(|e| e.to_compile_error());
+    quote!({ #(#errors)* }).into()
+}
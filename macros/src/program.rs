@@ -0,0 +1,252 @@
+use std::cell::Cell;
+use std::fmt::Display;
+use std::rc::Rc;
+use proc_macro::{Delimiter, Spacing, Span, TokenStream, TokenTree};
+use proc_macro2::Literal;
+use quote::{format_ident, quote};
+use crate::ast::{LiteralValue, Stmt};
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use crate::src::Index;
+use crate::token::{Token, TokenKind};
+use crate::types::Number;
+
+/// An error found in Lox code at compile time.
+pub struct Error {
+    pub offset: Index,
+    pub message: String,
+}
+
+impl Error {
+    fn new(offset: Index, error: impl Display) -> Self {
+        Error {
+            offset,
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Scans, parses and resolves `source` into statements ready to run, as `Lox::parse` does at runtime.
+pub fn parse(source: &[u8]) -> Result<Vec<Stmt>, Vec<Error>> {
+    let tokens = Scanner::new(source)
+        .scan_tokens()
+        .map_err(|errors| errors.iter().map(|e| Error::new(e.snippet().offset(), e)).collect::<Vec<_>>())?;
+
+    let stmts = Parser::new(&tokens)
+        .parse()
+        .map_err(|errors| errors.iter().map(|e| Error::new(e.snippet().offset(), e)).collect::<Vec<_>>())?;
+
+    Resolver::new()
+        .resolve(&stmts)
+        .map_err(|errors| errors.iter().map(|e| Error::new(e.snippet().offset(), e)).collect::<Vec<_>>())?;
+
+    Ok(stmts)
+}
+
+/// Quotes `stmts` parsed from `source` named `name` into Rust code building a `qlox::Program` of them,
+/// which builds the statements again from the offset the source is added at to run them.
+pub fn program(name: &str, source: &str, stmts: &[Stmt]) -> proc_macro2::TokenStream {
+    let stmts = stmts.iter().map(Quote::quote);
+    quote!(::qlox::Program::new(#name, #source, |_base| ::std::vec![#(#stmts),*]))
+}
+
+/// Lox code written as Rust tokens, which are joined into Lox source keeping the span of each token,
+/// so that an error in the source can be reported at the token it's found in.
+pub struct TokenSource {
+    pub text: String,
+    /// The offset of each token in the source, in order, with its span.
+    spans: Vec<(Index, Span)>,
+    line: usize,
+    joint: bool,
+}
+
+impl TokenSource {
+    pub fn new(stream: TokenStream) -> Self {
+        let mut source = TokenSource {
+            text: String::new(),
+            spans: Vec::new(),
+            line: 0,
+            joint: false,
+        };
+        source.push_stream(stream);
+        source
+    }
+
+    /// Returns the span of the token at `offset`, or of the last token if it's past the end.
+    pub fn span(&self, offset: Index) -> Span {
+        match self.spans.partition_point(|&(start, _)| start <= offset) {
+            0 => Span::call_site(),
+            i => self.spans[i - 1].1,
+        }
+    }
+
+    fn push_stream(&mut self, stream: TokenStream) {
+        for tree in stream {
+            match tree {
+                TokenTree::Group(group) => {
+                    let (open, close) = match group.delimiter() {
+                        Delimiter::Parenthesis => ("(", ")"),
+                        Delimiter::Brace => ("{", "}"),
+                        Delimiter::Bracket => ("[", "]"),
+                        Delimiter::None => ("", ""),
+                    };
+                    self.push(open, group.span_open(), false);
+                    self.push_stream(group.stream());
+                    self.push(close, group.span_close(), false);
+                },
+                TokenTree::Punct(punct) => {
+                    self.push(&punct.to_string(), punct.span(), punct.spacing() == Spacing::Joint);
+                },
+                tree => self.push(&tree.to_string(), tree.span(), false),
+            }
+        }
+    }
+
+    /// Appends a token, separated from the previous one by a line break if it's on a later line,
+    /// or by a space unless it's a punctuation joint to the previous one, such as `=` of `!=`.
+    fn push(&mut self, text: &str, span: Span, joint: bool) {
+        if text.is_empty() {
+            return;
+        }
+        if !self.text.is_empty() {
+            if span.line() > self.line {
+                self.text.push('\n');
+            } else if !self.joint {
+                self.text.push(' ');
+            }
+        }
+        self.spans.push((self.text.len(), span));
+        self.text.push_str(text);
+        self.line = span.line();
+        self.joint = joint;
+    }
+}
+
+/// A value parsed at compile time, which is quoted into Rust code building it again at runtime.
+pub trait Quote {
+    fn quote(&self) -> proc_macro2::TokenStream;
+}
+
+impl Quote for Token {
+    fn quote(&self) -> proc_macro2::TokenStream {
+        let Token { kind, lexeme, offset } = self;
+        let (kind, lexeme, offset) = (kind.quote(), lexeme.quote(), offset.quote());
+        // The offset is rebased onto `_base`, the parameter of the function `program` quotes.
+        quote!(::qlox::__private::ast::Token { kind: #kind, lexeme: #lexeme, offset: _base + #offset })
+    }
+}
+
+impl Quote for TokenKind {
+    fn quote(&self) -> proc_macro2::TokenStream {
+        match self {
+            TokenKind::String(s) => {
+                let s = s.quote();
+                quote!(::qlox::__private::ast::TokenKind::String(#s))
+            },
+            TokenKind::Number(n) => {
+                let n = n.quote();
+                quote!(::qlox::__private::ast::TokenKind::Number(#n))
+            },
+            TokenKind::StringPart(s) => {
+                let s = s.quote();
+                quote!(::qlox::__private::ast::TokenKind::StringPart(#s))
+            },
+            TokenKind::Identifier(s) => {
+                let s = s.quote();
+                quote!(::qlox::__private::ast::TokenKind::Identifier(#s))
+            },
+            // The other kinds have no fields, so they're named as they're debugged.
+            kind => {
+                let kind = format_ident!("{kind:?}");
+                quote!(::qlox::__private::ast::TokenKind::#kind)
+            },
+        }
+    }
+}
+
+impl Quote for LiteralValue {
+    fn quote(&self) -> proc_macro2::TokenStream {
+        match self {
+            LiteralValue::Number(n) => {
+                let n = n.quote();
+                quote!(::qlox::__private::ast::LiteralValue::Number(#n))
+            },
+            LiteralValue::String(s) => {
+                let s = s.quote();
+                quote!(::qlox::__private::ast::LiteralValue::String(#s))
+            },
+            LiteralValue::True => quote!(::qlox::__private::ast::LiteralValue::True),
+            LiteralValue::False => quote!(::qlox::__private::ast::LiteralValue::False),
+            LiteralValue::Nil => quote!(::qlox::__private::ast::LiteralValue::Nil),
+        }
+    }
+}
+
+impl Quote for String {
+    fn quote(&self) -> proc_macro2::TokenStream {
+        quote!(::std::string::String::from(#self))
+    }
+}
+
+impl Quote for Number {
+    fn quote(&self) -> proc_macro2::TokenStream {
+        // A number too big for `f64` is scanned as infinity, which has no literal.
+        match *self {
+            n if n.is_finite() => {
+                let n = Literal::f64_suffixed(n);
+                quote!(#n)
+            },
+            n if n.is_nan() => quote!(::std::primitive::f64::NAN),
+            n if n > 0.0 => quote!(::std::primitive::f64::INFINITY),
+            _ => quote!(::std::primitive::f64::NEG_INFINITY),
+        }
+    }
+}
+
+impl Quote for usize {
+    fn quote(&self) -> proc_macro2::TokenStream {
+        let n = Literal::usize_suffixed(*self);
+        quote!(#n)
+    }
+}
+
+impl<T: Quote + Copy> Quote for Cell<T> {
+    fn quote(&self) -> proc_macro2::TokenStream {
+        let value = self.get().quote();
+        quote!(::std::cell::Cell::new(#value))
+    }
+}
+
+impl<T: Quote> Quote for Option<T> {
+    fn quote(&self) -> proc_macro2::TokenStream {
+        match self {
+            Some(value) => {
+                let value = value.quote();
+                quote!(::std::option::Option::Some(#value))
+            },
+            None => quote!(::std::option::Option::None),
+        }
+    }
+}
+
+impl<T: Quote> Quote for Box<T> {
+    fn quote(&self) -> proc_macro2::TokenStream {
+        let value = T::quote(self);
+        quote!(::std::boxed::Box::new(#value))
+    }
+}
+
+impl<T: Quote> Quote for Vec<T> {
+    fn quote(&self) -> proc_macro2::TokenStream {
+        let values = self.iter().map(T::quote);
+        quote!(::std::vec![#(#values),*])
+    }
+}
+
+impl<T: Quote> Quote for Rc<[T]> {
+    fn quote(&self) -> proc_macro2::TokenStream {
+        let values = self.iter().map(T::quote);
+        quote!(::std::rc::Rc::<[_]>::from(::std::vec![#(#values),*]))
+    }
+}
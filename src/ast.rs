@@ -1,23 +1,44 @@
+use std::cell::Cell;
 use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
 use paste::paste;
 use crate::token::Token;
 use crate::types::Number;
 
+/// The number of scopes between a variable reference and the local variable it refers to,
+/// which is filled in by `Resolver`, and left `None` for a global variable.
+pub type Depth = Cell<Option<usize>>;
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum LiteralValue {
+    Number(Number),
+    String(String),
+    True,
+    False,
+    Nil,
+}
+
 pub trait Accept<V, C, R> {
     fn accept(&self, visitor: V, context: C) -> R;
 }
 
 macro_rules! ast {
-    ($(pub enum $base:ident { $($name:ident: $typ:ident $body:tt),* $(,)? })*) => { paste! {
+    ($(pub enum $base:ident {
+        $($name:ident: struct { $(pub $field:ident: $ftype:ty),* $(,)? }),* $(,)?
+    })*) => { paste! {
         $(
             #[derive(Debug, Clone, PartialEq)]
+            #[non_exhaustive]
             pub enum $base {
                 $($name($name)),*
             }
 
             $(
                 #[derive(Debug, Clone, PartialEq)]
-                pub $typ $name $body
+                pub struct $name {
+                    $(pub $field: $ftype),*
+                }
             )*
 
             pub trait [<$base Visitor>]<C, R> {
@@ -35,51 +56,240 @@ macro_rules! ast {
                     }
                 }
             }
+
+            // `qlox_macros` quotes the syntax tree parsed at compile time into Rust code building it.
+            #[cfg(qlox_macros)]
+            impl crate::program::Quote for $base {
+                fn quote(&self) -> proc_macro2::TokenStream {
+                    match self {
+                        $(
+                            $base::$name(x) => {
+                                let x = crate::program::Quote::quote(x);
+                                quote::quote!(::qlox::__private::ast::$base::$name(#x))
+                            }
+                        ),*
+                    }
+                }
+            }
+
+            $(
+                #[cfg(qlox_macros)]
+                impl crate::program::Quote for $name {
+                    fn quote(&self) -> proc_macro2::TokenStream {
+                        $(let $field = crate::program::Quote::quote(&self.$field);)*
+                        quote::quote!(::qlox::__private::ast::$name { $($field: #$field),* })
+                    }
+                }
+            )*
         )*
     } };
 }
 
 ast! {
     pub enum Expr {
+        Assign: struct {
+            pub name: Token,
+            pub value: Box<Expr>,
+            pub depth: Depth,
+        },
         Binary: struct {
             pub left: Box<Expr>,
             pub operator: Token,
             pub right: Box<Expr>,
         },
+        Call: struct {
+            pub callee: Box<Expr>,
+            pub paren: Token,
+            pub arguments: Vec<Expr>,
+        },
+        Get: struct {
+            pub object: Box<Expr>,
+            pub name: Token,
+        },
         Grouping: struct {
             pub expr: Box<Expr>,
         },
-        Literal: enum {
-            Number(Number),
-            String(String),
-            True,
-            False,
-            Nil,
+        Literal: struct {
+            pub value: LiteralValue,
+            pub token: Token,
+        },
+        Logical: struct {
+            pub left: Box<Expr>,
+            pub operator: Token,
+            pub right: Box<Expr>,
+        },
+        Set: struct {
+            pub object: Box<Expr>,
+            pub name: Token,
+            pub value: Box<Expr>,
+        },
+        Super: struct {
+            pub keyword: Token,
+            pub method: Token,
+            pub depth: Depth,
+        },
+        This: struct {
+            pub keyword: Token,
+            pub depth: Depth,
         },
         Unary: struct {
             pub operator: Token,
             pub right: Box<Expr>,
         },
+        Variable: struct {
+            pub name: Token,
+            pub depth: Depth,
+        },
+    }
+
+    pub enum Stmt {
+        Block: struct {
+            pub stmts: Vec<Stmt>,
+        },
+        Class: struct {
+            pub name: Token,
+            pub superclass: Option<Variable>,
+            pub methods: Vec<Function>,
+        },
+        Expression: struct {
+            pub expr: Expr,
+        },
+        Function: struct {
+            pub name: Token,
+            pub params: Vec<Token>,
+            pub body: Rc<[Stmt]>,
+        },
+        If: struct {
+            pub condition: Expr,
+            pub then_branch: Box<Stmt>,
+            pub else_branch: Option<Box<Stmt>>,
+        },
+        Print: struct {
+            pub expr: Expr,
+        },
+        Return: struct {
+            pub keyword: Token,
+            pub value: Option<Expr>,
+        },
+        Var: struct {
+            pub name: Token,
+            pub initializer: Option<Expr>,
+        },
+        While: struct {
+            pub condition: Expr,
+            pub body: Box<Stmt>,
+        },
     }
 }
 
 impl Display for Expr {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
+            Expr::Assign(Assign { name, value, .. }) => {
+                f.write_fmt(format_args!("(= {} {})", name, value))
+            },
             Expr::Binary(Binary { left, operator, right }) => {
                 f.write_fmt(format_args!("({} {} {})", operator, left, right))
             },
+            Expr::Call(Call { callee, arguments, .. }) => {
+                write!(f, "(call {callee}")?;
+                arguments.iter().try_for_each(|a| write!(f, " {a}"))?;
+                f.write_str(")")
+            },
+            Expr::Get(Get { object, name }) => {
+                f.write_fmt(format_args!("(. {} {})", object, name))
+            },
             Expr::Grouping(Grouping { expr }) => {
                 f.write_fmt(format_args!("(group {})", expr))
             },
-            Expr::Literal(Literal::Number(n)) => n.fmt(f),
-            Expr::Literal(Literal::String(s)) => s.fmt(f),
-            Expr::Literal(Literal::True) => f.write_str("true"),
-            Expr::Literal(Literal::False) => f.write_str("false"),
-            Expr::Literal(Literal::Nil) => f.write_str("nil"),
+            Expr::Literal(Literal { value, .. }) => match value {
+                LiteralValue::Number(n) => n.fmt(f),
+                LiteralValue::String(s) => s.fmt(f),
+                LiteralValue::True => f.write_str("true"),
+                LiteralValue::False => f.write_str("false"),
+                LiteralValue::Nil => f.write_str("nil"),
+            },
+            Expr::Logical(Logical { left, operator, right }) => {
+                f.write_fmt(format_args!("({} {} {})", operator, left, right))
+            },
+            Expr::Set(Set { object, name, value }) => {
+                f.write_fmt(format_args!("(= (. {} {}) {})", object, name, value))
+            },
+            Expr::Super(Super { method, .. }) => {
+                f.write_fmt(format_args!("(. super {})", method))
+            },
+            Expr::This(_) => f.write_str("this"),
             Expr::Unary(Unary { operator, right }) => {
                 f.write_fmt(format_args!("({} {})", operator, right))
             },
+            Expr::Variable(Variable { name, .. }) => name.fmt(f),
+        }
+    }
+}
+
+impl Display for Stmt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Stmt::Block(Block { stmts }) => {
+                f.write_str("(block")?;
+                stmts.iter().try_for_each(|s| write!(f, " {s}"))?;
+                f.write_str(")")
+            },
+            Stmt::Class(Class { name, superclass, methods }) => {
+                write!(f, "(class {name}")?;
+                if let Some(Variable { name, .. }) = superclass {
+                    write!(f, " < {name}")?;
+                }
+                methods.iter().try_for_each(|m| write!(f, " {m}"))?;
+                f.write_str(")")
+            },
+            Stmt::Expression(Expression { expr }) => {
+                f.write_fmt(format_args!("(; {})", expr))
+            },
+            Stmt::Function(function) => function.fmt(f),
+            Stmt::If(If { condition, then_branch, else_branch }) => {
+                write!(f, "(if {condition} {then_branch}")?;
+                if let Some(else_branch) = else_branch {
+                    write!(f, " {else_branch}")?;
+                }
+                f.write_str(")")
+            },
+            Stmt::Print(Print { expr }) => {
+                f.write_fmt(format_args!("(print {})", expr))
+            },
+            Stmt::Return(Return { value, .. }) => {
+                f.write_str("(return")?;
+                if let Some(value) = value {
+                    write!(f, " {value}")?;
+                }
+                f.write_str(")")
+            },
+            Stmt::Var(Var { name, initializer }) => {
+                write!(f, "(var {name}")?;
+                if let Some(initializer) = initializer {
+                    write!(f, " {initializer}")?;
+                }
+                f.write_str(")")
+            },
+            Stmt::While(While { condition, body }) => {
+                f.write_fmt(format_args!("(while {} {})", condition, body))
+            },
+        }
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Function { name, params, body } = self;
+        write!(f, "(fun {name} (")?;
+        for (i, param) in params.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            param.fmt(f)?;
         }
+        f.write_str(")")?;
+        body.iter().try_for_each(|s| write!(f, " {s}"))?;
+        f.write_str(")")
     }
 }
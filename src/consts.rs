@@ -1,13 +1,32 @@
 #![allow(unused)]
 
 pub mod exitcode {
-    pub const OK: i32          = 0;
-    pub const GENERIC_ERR: i32 = 1;
-    pub const USAGE_ERR: i32   = 2; // `clap` uses exit code 2 for usage error.
-    pub const IO_ERR: i32      = 3;
-    pub const SCANNER_ERR: i32 = 50;
+    pub const OK: i32           = 0;
+    pub const GENERIC_ERR: i32  = 1;
+    pub const USAGE_ERR: i32    = 2; // `clap` uses exit code 2 for usage error.
+    pub const IO_ERR: i32       = 3;
+    pub const SCANNER_ERR: i32  = 50;
+    pub const PARSER_ERR: i32   = 51;
+    pub const RESOLVER_ERR: i32 = 52;
+    pub const RUNTIME_ERR: i32  = 53;
+    pub const COMPILER_ERR: i32 = 54;
+    pub const LOXC_ERR: i32     = 55;
+}
+
+pub mod limit {
+    pub const MAX_ARGUMENTS: usize  = 255;
+    pub const MAX_CALL_DEPTH: usize = 255;
+    pub const MAX_LOCALS: usize     = 256;
+    pub const MAX_UPVALUES: usize   = 256;
+    pub const MAX_CONSTANTS: usize  = 65536;
+    pub const MAX_JUMP: usize       = 65535;
+}
+
+pub mod gc {
+    pub const DEFAULT_THRESHOLD: usize = 1024;
+    pub const GROWTH_FACTOR: usize     = 2;
 }
 
 pub mod tag {
-    pub const ERROR: &'static str = "\x1b[1;31merror\x1b[0m"; // red bold
+    pub const ERROR: &str = "\x1b[1;31merror\x1b[0m"; // red bold
 }
\ No newline at end of file
@@ -0,0 +1,208 @@
+use std::rc::Rc;
+use thiserror::Error;
+use crate::types::Number;
+use crate::value::Value;
+
+/// A Rust value which can be converted into a Lox value.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+/// A Rust value which can be converted from a Lox value, failing if the Lox value has another type.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self, ConversionError>;
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("expected {expected}, found {found}")]
+pub struct ConversionError {
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
+impl ConversionError {
+    fn new(expected: &'static str, value: &Value) -> Self {
+        ConversionError {
+            expected,
+            found: value.type_name(),
+        }
+    }
+}
+
+impl IntoValue for Value {
+    #[inline(always)]
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl FromValue for Value {
+    #[inline(always)]
+    fn from_value(value: &Value) -> Result<Self, ConversionError> {
+        Ok(value.clone())
+    }
+}
+
+impl IntoValue for () {
+    #[inline(always)]
+    fn into_value(self) -> Value {
+        Value::Nil
+    }
+}
+
+impl FromValue for () {
+    fn from_value(value: &Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::Nil => Ok(()),
+            _ => Err(ConversionError::new("nil", value)),
+        }
+    }
+}
+
+impl IntoValue for bool {
+    #[inline(always)]
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::Bool(b) => Ok(*b),
+            _ => Err(ConversionError::new("boolean", value)),
+        }
+    }
+}
+
+impl IntoValue for Number {
+    #[inline(always)]
+    fn into_value(self) -> Value {
+        Value::Number(self)
+    }
+}
+
+impl FromValue for Number {
+    fn from_value(value: &Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::Number(n) => Ok(*n),
+            _ => Err(ConversionError::new("number", value)),
+        }
+    }
+}
+
+/// Converts into a number, which is exact only within ±2^53.
+impl IntoValue for i64 {
+    #[inline(always)]
+    fn into_value(self) -> Value {
+        Value::Number(self as Number)
+    }
+}
+
+/// Converts from a number without a fractional part, which fits in `i64`.
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::Number(n) if n.fract() == 0.0 && *n >= i64::MIN as Number && *n < i64::MAX as Number => {
+                Ok(*n as i64)
+            },
+            _ => Err(ConversionError::new("integer", value)),
+        }
+    }
+}
+
+impl IntoValue for String {
+    #[inline(always)]
+    fn into_value(self) -> Value {
+        Value::String(self.into())
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self, ConversionError> {
+        Rc::<str>::from_value(value).map(|s| s.to_string())
+    }
+}
+
+impl IntoValue for &str {
+    #[inline(always)]
+    fn into_value(self) -> Value {
+        Value::String(self.into())
+    }
+}
+
+impl IntoValue for Rc<str> {
+    #[inline(always)]
+    fn into_value(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl FromValue for Rc<str> {
+    fn from_value(value: &Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::String(s) => Ok(s.clone()),
+            _ => Err(ConversionError::new("string", value)),
+        }
+    }
+}
+
+/// Converts `None` into `nil`.
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        self.map_or(Value::Nil, T::into_value)
+    }
+}
+
+/// Converts `nil` into `None`.
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::Nil => Ok(None),
+            _ => T::from_value(value).map(Some),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn into_value_from_value() {
+        assert_eq!(().into_value(), Value::Nil);
+        assert_eq!(true.into_value(), Value::Bool(true));
+        assert_eq!(1.5.into_value(), Value::Number(1.5));
+        assert_eq!((-3i64).into_value(), Value::Number(-3.0));
+        assert_eq!("a".into_value(), Value::String("a".into()));
+        assert_eq!(String::from("b").into_value(), Value::String("b".into()));
+        assert_eq!(None::<bool>.into_value(), Value::Nil);
+        assert_eq!(Some(2.0).into_value(), Value::Number(2.0));
+
+        assert_eq!(<()>::from_value(&Value::Nil), Ok(()));
+        assert_eq!(bool::from_value(&Value::Bool(false)), Ok(false));
+        assert_eq!(Number::from_value(&Value::Number(1.5)), Ok(1.5));
+        assert_eq!(i64::from_value(&Value::Number(-3.0)), Ok(-3));
+        assert_eq!(String::from_value(&Value::String("a".into())), Ok("a".to_string()));
+        assert_eq!(Option::<Number>::from_value(&Value::Nil), Ok(None));
+        assert_eq!(Option::<Number>::from_value(&Value::Number(2.0)), Ok(Some(2.0)));
+    }
+
+    #[test]
+    fn from_value_error() {
+        let cases = [
+            (<()>::from_value(&Value::Bool(false)).err(), "nil", "boolean"),
+            (bool::from_value(&Value::Nil).err(), "boolean", "nil"),
+            (Number::from_value(&Value::String("1".into())).err(), "number", "string"),
+            (i64::from_value(&Value::Number(1.5)).err(), "integer", "number"),
+            (i64::from_value(&Value::Number(1e19)).err(), "integer", "number"),
+            (String::from_value(&Value::Number(1.0)).err(), "string", "number"),
+            (Option::<bool>::from_value(&Value::Number(1.0)).err(), "boolean", "number"),
+        ];
+
+        for (error, expected, found) in cases {
+            assert_eq!(error, Some(ConversionError { expected, found }));
+        }
+        assert_eq!(ConversionError { expected: "number", found: "nil" }.to_string(), "expected number, found nil");
+    }
+}
@@ -0,0 +1,254 @@
+use std::error::Error as StdError;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::result;
+use crate::{Error, Lox, Result};
+use crate::ast::Stmt;
+use crate::convert::IntoValue;
+use crate::host::{HostClass, LoxClass};
+use crate::interpreter;
+use crate::src::{Index, SnippetResolver, SourceMap};
+use crate::value::{NativeFunction, Value};
+
+/// A Lox interpreter to embed in a Rust program, which runs Lox code on the tree-walker,
+/// keeping the global variables it defines between runs.
+pub struct Interpreter {
+    interpreter: interpreter::Interpreter,
+    /// The sources run so far, where each source evaluated is named `<eval:N>` in its order,
+    /// and each program run is named as it's named by `lox!` or `include_lox!`.
+    sources: SourceMap,
+    evals: usize,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+
+    /// Creates an interpreter whose `print` statements write to `out` instead of the standard output.
+    pub fn with_output(out: impl Write + 'static) -> Self {
+        Interpreter {
+            interpreter: interpreter::Interpreter::with_output(out),
+            sources: SourceMap::new(),
+            evals: 0,
+        }
+    }
+
+    /// Runs `source`, and returns the value of its last statement if it's an expression statement,
+    /// or nil otherwise.
+    pub fn eval(&mut self, source: &str) -> Result<Value> {
+        self.evals += 1;
+        let id = self.sources.add(format!("<eval:{}>", self.evals), source.as_bytes());
+        let stmts = Lox::parse(&self.sources, id)?;
+        self.interpreter
+            .interpret_value(&stmts)
+            .map_err(|e| SnippetResolver::new(&self.sources).resolve(e))
+            .map_err(Error::Runtime)
+    }
+
+    /// Runs `program`, and returns the value of its last statement as `eval` does.
+    /// Its source is added to the sources each time it runs, so that its errors are reported in its own file.
+    pub fn run(&mut self, program: &Program) -> Result<Value> {
+        let id = self.sources.add(program.name, program.source.as_bytes());
+        let stmts = (program.build)(self.sources.file(id).start());
+        self.interpreter
+            .interpret_value(&stmts)
+            .map_err(|e| SnippetResolver::new(&self.sources).resolve(e))
+            .map_err(Error::Runtime)
+    }
+
+    /// Binds the global variable `name` to `value`, redefining any global variable of the same name.
+    pub fn define(&mut self, name: &str, value: impl IntoValue) {
+        self.interpreter.define_global(name, value.into_value());
+    }
+
+    /// Returns the value of the global variable `name`, if it's defined.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.interpreter.get_global(name)
+    }
+
+    /// Defines the global native function `name`, which runs `function` with `arity` arguments.
+    /// An error returned by `function` fails the call with a runtime error showing its message.
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&[Value]) -> result::Result<Value, Box<dyn StdError>> + 'static,
+    {
+        let native = NativeFunction::new(name.to_string(), arity, Box::new(function));
+        let native = self.interpreter.heap().alloc(native);
+        self.interpreter.define_global(name, Value::Callable(native));
+    }
+
+    /// Defines the global class `T::NAME`, which creates an instance of the Rust struct `T` when called.
+    pub fn define_class<T: LoxClass>(&mut self) {
+        self.interpreter.define_global(T::NAME, Value::Callable(Rc::new(HostClass::<T>::new())));
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A Lox program checked and parsed at compile time by `lox!` or `include_lox!`,
+/// which runs without being scanned, parsed and resolved again.
+#[derive(Debug, Clone)]
+pub struct Program {
+    name: &'static str,
+    source: &'static str,
+    /// Builds the statements resolved from the source, with the offsets of their tokens rebased
+    /// onto the offset the source is added at in a `SourceMap`.
+    build: fn(Index) -> Vec<Stmt>,
+}
+
+impl Program {
+    /// Creates a program of the statements `build` builds from `source` named `name`,
+    /// which is called by the code `lox!` expands to.
+    #[doc(hidden)]
+    pub fn new(name: &'static str, source: &'static str, build: fn(Index) -> Vec<Stmt>) -> Self {
+        Program { name, source, build }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use super::*;
+    use crate::convert::FromValue;
+    use crate::interpreter::RuntimeError;
+    use crate::types::Number;
+
+    /// A shared buffer to capture the output of `print` statements.
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn interpreter_eval() {
+        let output = Output::default();
+        let mut interpreter = Interpreter::with_output(output.clone());
+        interpreter.define("greeting", "hello");
+        interpreter.define("count", 2.0);
+
+        assert_eq!(interpreter.eval("var a = count + 1; print greeting;").unwrap(), Value::Nil);
+        assert_eq!(interpreter.eval("a * 2;").unwrap(), Value::Number(6.0));
+        assert_eq!(interpreter.eval("fun f() {} f;").unwrap().to_string(), "<fn f>");
+        assert_eq!(interpreter.get("a"), Some(Value::Number(3.0)));
+        assert_eq!(interpreter.get("b"), None);
+        assert_eq!(String::from_utf8(output.0.take()).unwrap(), "hello\n");
+
+        assert!(matches!(interpreter.eval("print a"), Err(Error::Parser(_))));
+        assert!(matches!(interpreter.eval("print b;"), Err(Error::Runtime(_))));
+
+        // An error in a function is reported in the source it's declared in.
+        interpreter.eval("fun g() {\n  return -greeting;\n}").unwrap();
+        let Error::Runtime(error) = interpreter.eval("g();").err().unwrap() else {
+            panic!("`g` must fail at runtime");
+        };
+        let snippet = error.snippet();
+        assert_eq!((snippet.file_name(), snippet.code()), (Some("<eval:6>"), Some("  return -greeting;")));
+    }
+
+    #[test]
+    fn interpreter_define_native() {
+        let mut interpreter = Interpreter::with_output(Output::default());
+        interpreter.define_native("add", 2, |args| {
+            Ok((Number::from_value(&args[0])? + Number::from_value(&args[1])?).into_value())
+        });
+        interpreter.define_native("fail", 0, |_| Err("out of order".into()));
+
+        assert_eq!(interpreter.eval("add(1, add(2, 3));").unwrap(), Value::Number(6.0));
+        assert_eq!(interpreter.eval("add;").unwrap().to_string(), "<native fn add>");
+
+        let Error::Runtime(error) = interpreter.eval("add(1, 'a');").err().unwrap() else {
+            panic!("native function must fail at runtime");
+        };
+        let RuntimeError::Native { name, message, .. } = error else {
+            panic!("native function must fail with its own error");
+        };
+        assert_eq!((name.as_str(), message.as_str()), ("add", "expected number, found string"));
+
+        let Error::Runtime(error) = interpreter.eval("fail();").err().unwrap() else {
+            panic!("native function must fail at runtime");
+        };
+        assert!(error.to_string().contains("native function `fail` failed: out of order"));
+
+        let Error::Runtime(error) = interpreter.eval("add(1);").err().unwrap() else {
+            panic!("native function must be checked for its arity");
+        };
+        assert!(matches!(error, RuntimeError::ArityMismatch { expected: 2, found: 1, .. }));
+    }
+
+    #[test]
+    fn interpreter_run() {
+        let output = Output::default();
+        let mut interpreter = Interpreter::with_output(output.clone());
+        let program = crate::lox! {
+            class Counter {
+                init() { this.count = 0; }
+                increment() { this.count = this.count + 1; return this; }
+            }
+            var counter = Counter();
+            print "counting";
+            counter.increment().increment().count;
+        };
+        assert_eq!(interpreter.run(&program).unwrap(), Value::Number(2.0));
+        assert_eq!(interpreter.run(&program).unwrap(), Value::Number(2.0));
+
+        interpreter.run(&crate::include_lox!("../tests/lox/fibonacci.lox")).unwrap();
+        assert_eq!(interpreter.eval("fib(5);").unwrap(), Value::Number(5.0));
+        assert_eq!(String::from_utf8(output.0.take()).unwrap(), "counting\ncounting\n55\n");
+
+        let Error::Runtime(error) = interpreter.run(&crate::lox! { print -"a"; }).err().unwrap() else {
+            panic!("program must fail at runtime");
+        };
+        assert_eq!(error.snippet().code(), Some("print - \"a\" ;"));
+
+        // A number too big for `f64` is infinity, as it is when it's scanned at runtime.
+        let program = crate::lox! {
+            1000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000;
+        };
+        assert_eq!(interpreter.run(&program).unwrap(), Value::Number(f64::INFINITY));
+    }
+
+    #[test]
+    fn interpreter_run_error() {
+        let mut interpreter = Interpreter::with_output(Output::default());
+        let lox = |line: u32| format!("<lox:{}:{line}>", file!());
+        interpreter.eval("fun f() {\n  return -\"f\";\n}").unwrap();
+        interpreter.run(&crate::lox! { fun g() { return -"g"; } }).unwrap();
+        let g = lox(line!() - 1);
+        interpreter.run(&crate::include_lox!("../tests/lox/fibonacci.lox")).unwrap();
+
+        // Each error is found in the file its function is declared in, whether it's evaluated or run.
+        let cases = [
+            (crate::lox! { f(); }, "<eval:1>".to_string(), 1),
+            (crate::lox! { g(); }, g, 0),
+            (crate::lox! {
+                print 1;
+                print -nil;
+            }, lox(line!() - 3), 1),
+        ];
+        for (program, name, line) in cases {
+            let Error::Runtime(error) = interpreter.run(&program).err().unwrap() else {
+                panic!("program must fail at runtime");
+            };
+            let snippet = error.snippet();
+            assert_eq!((snippet.file_name(), snippet.position().map(|p| p.line)), (Some(name.as_str()), Some(line)));
+        }
+
+        let Error::Runtime(error) = interpreter.eval("fib(nil);").err().unwrap() else {
+            panic!("`fib` must fail at runtime");
+        };
+        assert_eq!(error.snippet().file_name(), Some("src/../tests/lox/fibonacci.lox"));
+    }
+}
@@ -0,0 +1,180 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::gc::{Trace, Tracer};
+use crate::interpreter::RuntimeError;
+use crate::src::Snippet;
+use crate::token::Token;
+use crate::value::Value;
+
+/// A scope of variables, chained to the scope enclosing it.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// Binds `name` to `value` in this scope, shadowing or redefining any variable of the same name.
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    /// Looks `name` up from this scope outwards.
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        match self.values.get(&name.lexeme) {
+            Some(value) => Ok(value.clone()),
+            None => match &self.enclosing {
+                Some(enclosing) => enclosing.borrow().get(name),
+                None => Err(undefined_variable(name)),
+            },
+        }
+    }
+
+    /// Looks `name` up in the scope `distance` hops outwards from `environment`, as resolved by `Resolver`.
+    pub fn get_at(environment: &Rc<RefCell<Environment>>, distance: usize, name: &Token) -> Result<Value, RuntimeError> {
+        Self::ancestor(environment, distance)
+            .borrow()
+            .get_own(&name.lexeme)
+            .ok_or_else(|| undefined_variable(name))
+    }
+
+    /// Rebinds `name` in the scope `distance` hops outwards from `environment`, as resolved by `Resolver`.
+    pub fn assign_at(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+        name: &Token,
+        value: Value,
+    ) -> Result<(), RuntimeError> {
+        match Self::ancestor(environment, distance).borrow_mut().values.get_mut(&name.lexeme) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            },
+            None => Err(undefined_variable(name)),
+        }
+    }
+
+    /// Returns the scope `distance` hops outwards from `environment`.
+    pub fn ancestor(environment: &Rc<RefCell<Environment>>, distance: usize) -> Rc<RefCell<Environment>> {
+        let mut environment = environment.clone();
+        for _ in 0..distance {
+            let enclosing = environment.borrow().enclosing.clone();
+            environment = enclosing.expect("resolved scope must be enclosed by as many scopes as its depth");
+        }
+        environment
+    }
+
+    /// Looks `name` up in this scope only.
+    pub fn get_own(&self, name: &str) -> Option<Value> {
+        self.values.get(name).cloned()
+    }
+
+    /// Rebinds the innermost variable `name` visible from this scope, which must already be defined.
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        match self.values.get_mut(&name.lexeme) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            },
+            None => match &self.enclosing {
+                Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+                None => Err(undefined_variable(name)),
+            },
+        }
+    }
+}
+
+impl Trace for Environment {
+    fn trace(&self, tracer: &mut Tracer) {
+        for value in self.values.values() {
+            value.trace(tracer);
+        }
+        if let Some(enclosing) = &self.enclosing {
+            tracer.visit(enclosing);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+        self.enclosing = None;
+    }
+}
+
+fn undefined_variable(name: &Token) -> RuntimeError {
+    RuntimeError::UndefinedVariable {
+        snippet: Snippet::new(name.range()),
+        name: name.lexeme.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::TokenKind;
+
+    fn new_identifier(name: &str, offset: usize) -> Token {
+        Token {
+            kind: TokenKind::Identifier(name.to_string()),
+            lexeme: name.to_string(),
+            offset,
+        }
+    }
+
+    #[test]
+    fn environment_get_assign() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("a", Value::Number(1.0));
+        globals.borrow_mut().define("b", Value::Number(2.0));
+
+        let mut locals = Environment::with_enclosing(globals.clone());
+        locals.define("a", Value::Bool(true));
+        assert_eq!(locals.get(&new_identifier("a", 0)), Ok(Value::Bool(true)));
+        assert_eq!(locals.get(&new_identifier("b", 0)), Ok(Value::Number(2.0)));
+
+        locals.assign(&new_identifier("b", 0), Value::Nil).unwrap();
+        assert_eq!(globals.borrow().get(&new_identifier("b", 0)), Ok(Value::Nil));
+        assert_eq!(globals.borrow().get(&new_identifier("a", 0)), Ok(Value::Number(1.0)));
+
+        assert_eq!(locals.get(&new_identifier("c", 3)), Err(RuntimeError::UndefinedVariable {
+            snippet: Snippet::new(3..4),
+            name: "c".to_string(),
+        }));
+        assert_eq!(locals.assign(&new_identifier("c", 5), Value::Nil), Err(RuntimeError::UndefinedVariable {
+            snippet: Snippet::new(5..6),
+            name: "c".to_string(),
+        }));
+    }
+
+    #[test]
+    fn environment_get_at_assign_at() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("a", Value::Number(1.0));
+        let locals = Rc::new(RefCell::new(Environment::with_enclosing(globals.clone())));
+        locals.borrow_mut().define("a", Value::Number(2.0));
+        let inner = Rc::new(RefCell::new(Environment::with_enclosing(locals.clone())));
+
+        assert_eq!(Environment::get_at(&inner, 1, &new_identifier("a", 0)), Ok(Value::Number(2.0)));
+        assert_eq!(Environment::get_at(&inner, 2, &new_identifier("a", 0)), Ok(Value::Number(1.0)));
+
+        Environment::assign_at(&inner, 2, &new_identifier("a", 0), Value::Nil).unwrap();
+        assert_eq!(globals.borrow().get(&new_identifier("a", 0)), Ok(Value::Nil));
+        assert_eq!(locals.borrow().get(&new_identifier("a", 0)), Ok(Value::Number(2.0)));
+
+        assert_eq!(Environment::get_at(&inner, 0, &new_identifier("a", 7)), Err(RuntimeError::UndefinedVariable {
+            snippet: Snippet::new(7..8),
+            name: "a".to_string(),
+        }));
+    }
+}
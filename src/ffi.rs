@@ -0,0 +1,308 @@
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use crate::consts::exitcode;
+use crate::embed::Interpreter;
+use crate::value::Value;
+
+// The statuses returned by the C API, which are the exit codes of `qlox`. They're spelled out for cbindgen,
+// which can't evaluate paths, so they're checked against `exitcode` below.
+
+/// Success.
+pub const QLOX_OK: i32 = 0;
+/// An unexpected failure, such as a panic, which is kept from unwinding into C.
+pub const QLOX_GENERIC_ERR: i32 = 1;
+/// A null or non-UTF-8 argument.
+pub const QLOX_USAGE_ERR: i32 = 2;
+pub const QLOX_IO_ERR: i32 = 3;
+pub const QLOX_SCANNER_ERR: i32 = 50;
+pub const QLOX_PARSER_ERR: i32 = 51;
+pub const QLOX_RESOLVER_ERR: i32 = 52;
+pub const QLOX_RUNTIME_ERR: i32 = 53;
+pub const QLOX_COMPILER_ERR: i32 = 54;
+pub const QLOX_LOXC_ERR: i32 = 55;
+
+const _: () = assert!(
+    QLOX_OK == exitcode::OK
+        && QLOX_GENERIC_ERR == exitcode::GENERIC_ERR
+        && QLOX_USAGE_ERR == exitcode::USAGE_ERR
+        && QLOX_IO_ERR == exitcode::IO_ERR
+        && QLOX_SCANNER_ERR == exitcode::SCANNER_ERR
+        && QLOX_PARSER_ERR == exitcode::PARSER_ERR
+        && QLOX_RESOLVER_ERR == exitcode::RESOLVER_ERR
+        && QLOX_RUNTIME_ERR == exitcode::RUNTIME_ERR
+        && QLOX_COMPILER_ERR == exitcode::COMPILER_ERR
+        && QLOX_LOXC_ERR == exitcode::LOXC_ERR
+);
+
+/// An interpreter owned by C code, which keeps the diagnostics of its last run.
+pub struct QloxInterpreter {
+    interpreter: Interpreter,
+    diagnostics: CString,
+}
+
+/// The type of a `QloxValue`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QloxValueKind {
+    Nil,
+    Bool,
+    Number,
+    String,
+    /// A function, a class or an instance, which is passed to C as the string it's printed as.
+    Object,
+}
+
+/// A Lox value passed between C and Lox, whose fields other than `kind` are read only for the kinds they're for.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct QloxValue {
+    pub kind: QloxValueKind,
+    pub boolean: bool,
+    pub number: f64,
+    /// A NUL-terminated UTF-8 string for `String` and `Object`.
+    pub string: *const c_char,
+}
+
+/// A C function called as a native Lox function with `arg_count` arguments in `args`, which are valid only
+/// during the call. It writes its return value into `result`, and returns 0 on success. On failure, it returns
+/// any other value, and may write a `String` value into `result` as the message of the runtime error.
+/// A string written into `result` is copied as soon as the function returns.
+pub type QloxNativeFn = unsafe extern "C" fn(
+    user_data: *mut c_void,
+    args: *const QloxValue,
+    arg_count: usize,
+    result: *mut QloxValue,
+) -> i32;
+
+/// Creates an interpreter, which must be freed by `qlox_free`, or returns null if it fails.
+#[unsafe(no_mangle)]
+pub extern "C" fn qlox_new() -> *mut QloxInterpreter {
+    catch_panic(|| {
+        Box::into_raw(Box::new(QloxInterpreter {
+            interpreter: Interpreter::new(),
+            diagnostics: CString::default(),
+        }))
+    }).unwrap_or(ptr::null_mut())
+}
+
+/// Frees an interpreter created by `qlox_new`.
+///
+/// # Safety
+/// `interpreter` must be null, or created by `qlox_new` and not freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn qlox_free(interpreter: *mut QloxInterpreter) {
+    if !interpreter.is_null() {
+        // A panic while dropping leaks whatever is left of the interpreter.
+        let _ = catch_panic(|| drop(unsafe { Box::from_raw(interpreter) }));
+    }
+}
+
+/// Runs the NUL-terminated UTF-8 `source`, and returns the exit code `qlox` would exit with for it,
+/// which is `QLOX_OK` on success, or `QLOX_GENERIC_ERR` if the interpreter panics.
+/// The diagnostics of a failure are kept until the next run.
+///
+/// # Safety
+/// `interpreter` must be created by `qlox_new`, and `source` must be null or a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn qlox_eval(interpreter: *mut QloxInterpreter, source: *const c_char) -> i32 {
+    let interpreter = unsafe { &mut *interpreter };
+    if source.is_null() {
+        interpreter.set_diagnostics("source is null".to_string());
+        return QLOX_USAGE_ERR;
+    }
+
+    let result = match unsafe { CStr::from_ptr(source) }.to_str() {
+        Ok(source) => catch_panic(|| interpreter.interpreter.eval(source)),
+        Err(e) => {
+            interpreter.set_diagnostics(format!("source is not valid UTF-8: {e}"));
+            return QLOX_USAGE_ERR;
+        },
+    };
+    match result {
+        Ok(Ok(_)) => {
+            interpreter.set_diagnostics(String::new());
+            QLOX_OK
+        },
+        Ok(Err(e)) => {
+            interpreter.set_diagnostics(e.to_string());
+            e.exit_code()
+        },
+        Err(message) => {
+            interpreter.set_diagnostics(format!("qlox panicked: {message}"));
+            QLOX_GENERIC_ERR
+        },
+    }
+}
+
+/// Returns the diagnostics of the last run as a NUL-terminated string, which is empty if it succeeded.
+/// The string is owned by `interpreter`, and valid until its next run.
+///
+/// # Safety
+/// `interpreter` must be created by `qlox_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn qlox_diagnostics(interpreter: *const QloxInterpreter) -> *const c_char {
+    catch_panic(|| unsafe { &*interpreter }.diagnostics.as_ptr()).unwrap_or(c"".as_ptr())
+}
+
+/// Defines the global native function `name`, which calls `function` with `user_data` and `arity` arguments.
+/// Returns `QLOX_OK` on success, `QLOX_USAGE_ERR` if `name` is null or not valid UTF-8,
+/// or `QLOX_GENERIC_ERR` if the interpreter panics.
+///
+/// # Safety
+/// `interpreter` must be created by `qlox_new`, and `name` must be null or a NUL-terminated string.
+/// `user_data` must be valid for `function` as long as `interpreter` is alive.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn qlox_define_native(
+    interpreter: *mut QloxInterpreter,
+    name: *const c_char,
+    arity: usize,
+    function: QloxNativeFn,
+    user_data: *mut c_void,
+) -> i32 {
+    let interpreter = unsafe { &mut *interpreter };
+    let Some(name) = (!name.is_null()).then(|| unsafe { CStr::from_ptr(name) }.to_str().ok()).flatten() else {
+        return QLOX_USAGE_ERR;
+    };
+
+    let defined = catch_panic(|| interpreter.interpreter.define_native(name, arity, move |args| {
+        // Keeps the strings of the arguments alive during the call.
+        let strings = args.iter().map(to_c_string).collect::<Vec<_>>();
+        let args = args.iter().zip(&strings).map(|(arg, string)| to_c_value(arg, string)).collect::<Vec<_>>();
+
+        let mut result = QloxValue::nil();
+        let status = unsafe { function(user_data, args.as_ptr(), args.len(), &mut result) };
+        match status {
+            0 => unsafe { from_c_value(&result) }.ok_or_else(|| "returned an object".into()),
+            _ => Err(match unsafe { from_c_value(&result) } {
+                Some(Value::String(message)) => message.to_string(),
+                _ => format!("returned {status}"),
+            }.into()),
+        }
+    }));
+    defined.map_or(QLOX_GENERIC_ERR, |_| QLOX_OK)
+}
+
+/// Runs `f`, and returns the message of its panic if it panics, which must not unwind into C.
+fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => payload.downcast_ref::<&str>().map_or("unknown panic", |message| message).to_string(),
+        }
+    })
+}
+
+impl QloxInterpreter {
+    fn set_diagnostics(&mut self, diagnostics: String) {
+        // The diagnostics can't contain NUL, unless the source does in a snippet, where it's cut off.
+        self.diagnostics = CString::new(diagnostics).unwrap_or_else(|e| {
+            let end = e.nul_position();
+            CString::new(&e.into_vec()[..end]).unwrap_or_default()
+        });
+    }
+}
+
+impl QloxValue {
+    #[inline(always)]
+    fn nil() -> Self {
+        QloxValue {
+            kind: QloxValueKind::Nil,
+            boolean: false,
+            number: 0.0,
+            string: ptr::null(),
+        }
+    }
+}
+
+/// Returns the string of a string or an object, which a `QloxValue` points to.
+fn to_c_string(value: &Value) -> Option<CString> {
+    match value {
+        Value::Nil | Value::Bool(_) | Value::Number(_) => None,
+        value => CString::new(value.to_string().replace('\0', "")).ok(),
+    }
+}
+
+fn to_c_value(value: &Value, string: &Option<CString>) -> QloxValue {
+    let string = string.as_ref().map_or(ptr::null(), |s| s.as_ptr());
+    match value {
+        Value::Nil => QloxValue::nil(),
+        Value::Bool(b) => QloxValue { kind: QloxValueKind::Bool, boolean: *b, ..QloxValue::nil() },
+        Value::Number(n) => QloxValue { kind: QloxValueKind::Number, number: *n, ..QloxValue::nil() },
+        Value::String(_) => QloxValue { kind: QloxValueKind::String, string, ..QloxValue::nil() },
+        _ => QloxValue { kind: QloxValueKind::Object, string, ..QloxValue::nil() },
+    }
+}
+
+/// Converts a value returned from C, or returns `None` for an object, which C can't create.
+///
+/// # Safety
+/// The string of a `String` value must be null or a NUL-terminated string.
+unsafe fn from_c_value(value: &QloxValue) -> Option<Value> {
+    match value.kind {
+        QloxValueKind::Nil => Some(Value::Nil),
+        QloxValueKind::Bool => Some(Value::Bool(value.boolean)),
+        QloxValueKind::Number => Some(Value::Number(value.number)),
+        QloxValueKind::String if value.string.is_null() => Some(Value::String("".into())),
+        QloxValueKind::String => {
+            Some(Value::String(unsafe { CStr::from_ptr(value.string) }.to_string_lossy().into()))
+        },
+        QloxValueKind::Object => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::slice;
+    use super::*;
+
+    unsafe extern "C" fn add(
+        user_data: *mut c_void,
+        args: *const QloxValue,
+        arg_count: usize,
+        result: *mut QloxValue,
+    ) -> i32 {
+        let args = unsafe { slice::from_raw_parts(args, arg_count) };
+        let calls = unsafe { &mut *(user_data as *mut usize) };
+        *calls += 1;
+        let result = unsafe { &mut *result };
+        if args.iter().any(|a| a.kind != QloxValueKind::Number) {
+            *result = QloxValue { kind: QloxValueKind::String, string: c"expected numbers".as_ptr(), ..QloxValue::nil() };
+            return 1;
+        }
+        *result = QloxValue { kind: QloxValueKind::Number, number: args[0].number + args[1].number, ..QloxValue::nil() };
+        0
+    }
+
+    #[test]
+    fn qlox_eval_define_native() {
+        let mut calls = 0usize;
+        unsafe {
+            let interpreter = qlox_new();
+            let user_data = &mut calls as *mut usize as *mut c_void;
+            assert_eq!(qlox_define_native(interpreter, c"add".as_ptr(), 2, add, user_data), QLOX_OK);
+            assert_eq!(qlox_define_native(interpreter, ptr::null(), 2, add, user_data), QLOX_USAGE_ERR);
+
+            assert_eq!(qlox_eval(interpreter, c"var a = add(1, 2);".as_ptr()), QLOX_OK);
+            assert_eq!(CStr::from_ptr(qlox_diagnostics(interpreter)), c"");
+            assert_eq!((*interpreter).interpreter.get("a"), Some(Value::Number(3.0)));
+
+            assert_eq!(qlox_eval(interpreter, c"add(1, nil);".as_ptr()), QLOX_RUNTIME_ERR);
+            let diagnostics = CStr::from_ptr(qlox_diagnostics(interpreter)).to_str().unwrap();
+            assert!(diagnostics.contains("native function `add` failed: expected numbers"));
+
+            assert_eq!(qlox_eval(interpreter, c"var b = ;".as_ptr()), QLOX_PARSER_ERR);
+            assert_eq!(qlox_eval(interpreter, c"print @;".as_ptr()), QLOX_SCANNER_ERR);
+            assert_eq!(qlox_eval(interpreter, ptr::null()), QLOX_USAGE_ERR);
+            qlox_free(interpreter);
+        }
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn ffi_catch_panic() {
+        assert_eq!(catch_panic(|| 1), Ok(1));
+        assert_eq!(catch_panic(|| panic!("static")), Err::<(), _>("static".to_string()));
+        assert_eq!(catch_panic(|| panic!("formatted {}", 1)), Err::<(), _>("formatted 1".to_string()));
+    }
+}
@@ -0,0 +1,298 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use crate::consts::gc::{DEFAULT_THRESHOLD, GROWTH_FACTOR};
+
+/// An object which refers to other objects in the heap.
+pub trait Trace {
+    /// Visits every reference this holds to other objects, exactly once per reference.
+    fn trace(&self, tracer: &mut Tracer);
+
+    /// Drops every reference this holds to other objects, which breaks the cycles this is in
+    /// once it's garbage. Only mutable objects, allocated by `Heap::alloc_cell`, are cleared,
+    /// since every cycle must go through an object mutated after its creation.
+    fn clear(&mut self) {}
+}
+
+/// Visits the references held by an object.
+pub struct Tracer<'a> {
+    visit: &'a mut dyn FnMut(*const ()),
+    /// Whether the object is borrowed, so that its references couldn't be visited.
+    pinned: bool,
+}
+
+impl Tracer<'_> {
+    #[inline(always)]
+    pub fn visit<T: ?Sized>(&mut self, object: &Rc<T>) {
+        (self.visit)(Rc::as_ptr(object) as *const ());
+    }
+}
+
+/// The heap of objects of a backend, which collects the cycles of garbage `Rc` can't free on its own.
+///
+/// Objects are shared by `Rc`, so that the Rust code running Lox code can hold them freely,
+/// and the heap tracks them by `Weak` to collect them with a tracing mark-and-sweep:
+/// - The roots are the objects referenced from outside the heap, such as from the environments
+///   or the stack and the global variables of the backend, or from values in flight in Rust code.
+///   They're found by counting the references among objects, where an object referenced more times
+///   than by other objects must be referenced from outside, too.
+/// - The objects reachable from the roots are marked live, and the rest are swept by clearing them,
+///   which frees them as the last references to them are dropped.
+#[derive(Debug)]
+pub struct Heap {
+    objects: Vec<Object>,
+    config: GcConfig,
+    /// The number of objects to allocate, which triggers the next collection.
+    next_gc: usize,
+}
+
+/// How often a heap is collected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GcConfig {
+    /// The number of objects which triggers the first collection, after which a collection is triggered
+    /// whenever the heap grows to `GROWTH_FACTOR` times the objects surviving the last one, if it's more.
+    pub threshold: usize,
+    /// Whether to collect on every allocation, which makes bugs of missing references show up early.
+    pub stress: bool,
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        GcConfig {
+            threshold: DEFAULT_THRESHOLD,
+            stress: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Object {
+    Immutable(Weak<dyn Trace>),
+    Mutable(Weak<dyn TraceCell>),
+}
+
+/// A mutable object, which can be borrowed at the time of a collection.
+trait TraceCell {
+    fn trace(&self, tracer: &mut Tracer);
+    fn clear(&self);
+}
+
+impl<T: Trace> TraceCell for RefCell<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self.try_borrow() {
+            Ok(object) => object.trace(tracer),
+            Err(_) => tracer.pinned = true,
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut object) = self.try_borrow_mut() {
+            object.clear();
+        }
+    }
+}
+
+/// An object upgraded from the heap while collecting it.
+enum Live {
+    Immutable(Rc<dyn Trace>),
+    Mutable(Rc<dyn TraceCell>),
+}
+
+impl Live {
+    fn address(&self) -> *const () {
+        match self {
+            Live::Immutable(object) => Rc::as_ptr(object) as *const (),
+            Live::Mutable(object) => Rc::as_ptr(object) as *const (),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Live::Immutable(object) => Rc::strong_count(object),
+            Live::Mutable(object) => Rc::strong_count(object),
+        }
+    }
+
+    /// Calls `visit` on every reference of the object, and returns whether it's pinned.
+    fn trace(&self, visit: &mut dyn FnMut(*const ())) -> bool {
+        let mut tracer = Tracer { visit, pinned: false };
+        match self {
+            Live::Immutable(object) => object.trace(&mut tracer),
+            Live::Mutable(object) => object.trace(&mut tracer),
+        }
+        tracer.pinned
+    }
+
+    fn downgrade(&self) -> Object {
+        match self {
+            Live::Immutable(object) => Object::Immutable(Rc::downgrade(object)),
+            Live::Mutable(object) => Object::Mutable(Rc::downgrade(object)),
+        }
+    }
+}
+
+impl Heap {
+    pub fn new(config: GcConfig) -> Self {
+        Heap {
+            objects: Vec::new(),
+            config,
+            next_gc: config.threshold,
+        }
+    }
+
+    /// Allocates an object which can't be mutated after its creation.
+    pub fn alloc<T: Trace + 'static>(&mut self, object: T) -> Rc<T> {
+        self.collect_if_needed();
+        let object = Rc::new(object);
+        self.objects.push(Object::Immutable(Rc::downgrade(&object) as Weak<dyn Trace>));
+        object
+    }
+
+    /// Allocates a mutable object.
+    pub fn alloc_cell<T: Trace + 'static>(&mut self, object: T) -> Rc<RefCell<T>> {
+        self.collect_if_needed();
+        let object = Rc::new(RefCell::new(object));
+        self.objects.push(Object::Mutable(Rc::downgrade(&object) as Weak<dyn TraceCell>));
+        object
+    }
+
+    /// Collects before an allocation if the heap has grown enough, or on every allocation in stress mode.
+    fn collect_if_needed(&mut self) {
+        if self.config.stress || self.objects.len() >= self.next_gc {
+            self.collect();
+        }
+    }
+
+    /// Frees every object unreachable from the roots, and returns the number of objects still alive.
+    pub fn collect(&mut self) -> usize {
+        let objects = self.objects
+            .iter()
+            .filter_map(|object| match object {
+                Object::Immutable(object) => object.upgrade().map(Live::Immutable),
+                Object::Mutable(object) => object.upgrade().map(Live::Mutable),
+            })
+            .collect::<Vec<_>>();
+        let indices = objects
+            .iter()
+            .enumerate()
+            .map(|(i, object)| (object.address(), i))
+            .collect::<HashMap<_, _>>();
+
+        let mut references = vec![0; objects.len()];
+        let mut pinned = vec![false; objects.len()];
+        for (i, object) in objects.iter().enumerate() {
+            pinned[i] = object.trace(&mut |address| {
+                if let Some(&j) = indices.get(&address) {
+                    references[j] += 1;
+                }
+            });
+        }
+
+        // Every object is referenced once more by `objects` itself.
+        let mut marked = objects
+            .iter()
+            .enumerate()
+            .map(|(i, object)| pinned[i] || object.strong_count() - 1 > references[i])
+            .collect::<Vec<_>>();
+        let mut gray = (0..objects.len()).filter(|&i| marked[i]).collect::<Vec<_>>();
+        while let Some(i) = gray.pop() {
+            objects[i].trace(&mut |address| {
+                if let Some(&j) = indices.get(&address) && !marked[j] {
+                    marked[j] = true;
+                    gray.push(j);
+                }
+            });
+        }
+
+        self.objects.clear();
+        for (object, marked) in objects.iter().zip(marked) {
+            match object {
+                _ if marked => self.objects.push(object.downgrade()),
+                Live::Mutable(object) => object.clear(),
+                Live::Immutable(_) => {},
+            }
+        }
+        self.next_gc = self.config.threshold.max(self.objects.len() * GROWTH_FACTOR);
+        self.objects.len()
+    }
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new(GcConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Node {
+        next: Option<Rc<RefCell<Node>>>,
+    }
+
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            if let Some(next) = &self.next {
+                tracer.visit(next);
+            }
+        }
+
+        fn clear(&mut self) {
+            self.next = None;
+        }
+    }
+
+    #[test]
+    fn heap_collect() {
+        let mut heap = Heap::default();
+        let a = heap.alloc_cell(Node::default());
+        let b = heap.alloc_cell(Node { next: Some(a.clone()) });
+        a.borrow_mut().next = Some(b.clone());
+        let (weak_a, weak_b) = (Rc::downgrade(&a), Rc::downgrade(&b));
+
+        let c = heap.alloc_cell(Node::default());
+        let d = heap.alloc_cell(Node { next: Some(c.clone()) });
+        c.borrow_mut().next = Some(d.clone());
+        drop(d);
+
+        let e = heap.alloc_cell(Node::default());
+        let f = heap.alloc_cell(Node { next: Some(e.clone()) });
+        e.borrow_mut().next = Some(f.clone());
+        drop(f);
+        let pinned = e.borrow();
+
+        assert_eq!(heap.collect(), 6);
+        drop(a);
+        drop(b);
+        assert_eq!(heap.collect(), 4);
+        assert!(weak_a.upgrade().is_none() && weak_b.upgrade().is_none());
+
+        // A reachable cycle stays alive, as does a borrowed one.
+        assert!(c.borrow().next.as_ref().unwrap().borrow().next.is_some());
+        drop(pinned);
+        assert!(e.borrow().next.is_some());
+    }
+
+    #[test]
+    fn heap_collect_if_needed() {
+        let mut heap = Heap::new(GcConfig { threshold: 2, stress: false });
+        let nodes = (0..4).map(|_| heap.alloc_cell(Node::default())).collect::<Vec<_>>();
+        // Collected on the 3rd allocation, where 2 objects survived, so the heap can grow up to 4 objects.
+        assert_eq!((heap.objects.len(), heap.next_gc), (4, 4));
+
+        let node = heap.alloc_cell(Node::default());
+        node.borrow_mut().next = Some(node.clone());
+        drop(node);
+        assert_eq!((heap.objects.len(), heap.next_gc), (5, 8));
+        assert_eq!(heap.collect(), nodes.len());
+
+        let mut heap = Heap::new(GcConfig { threshold: 2, stress: true });
+        for _ in 0..3 {
+            let node = heap.alloc_cell(Node::default());
+            node.borrow_mut().next = Some(node.clone());
+        }
+        assert_eq!(heap.objects.len(), 1);
+    }
+}
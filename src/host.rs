@@ -0,0 +1,330 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::fmt::{self, Display, Formatter};
+use std::marker::PhantomData;
+use std::rc::Rc;
+use thiserror::Error;
+use crate::convert::{ConversionError, FromValue};
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::src::Snippet;
+use crate::token::Token;
+use crate::value::{Callable, Value};
+
+/// A Rust struct exposed to Lox as a class, which is implemented by `#[derive(LoxClass)]`.
+///
+/// Calling the class in Lox creates an instance from its fields in order, and the fields are
+/// readable and writable as properties of the instance. The methods of an impl block marked
+/// with `#[lox_methods]` are callable as methods of the instance.
+pub trait LoxClass: Sized + 'static {
+    const NAME: &'static str;
+    /// The number of arguments of the constructor.
+    const ARITY: usize;
+
+    fn construct(arguments: &[Value]) -> Result<Self, ArgumentError>;
+
+    /// Returns the value of the property `name`, if it's a field.
+    fn get(&self, name: &str) -> Option<Value>;
+
+    /// Sets the property `name` to `value`, if it's a field, failing if `value` has another type.
+    fn set(&mut self, name: &str, value: &Value) -> Option<Result<(), ConversionError>>;
+
+    fn methods() -> &'static [LoxMethod<Self>];
+}
+
+/// A method of a `LoxClass`, which converts its arguments from Lox values.
+pub struct LoxMethod<T> {
+    pub name: &'static str,
+    pub arity: usize,
+    pub call: fn(&mut T, &[Value]) -> Result<Value, ArgumentError>,
+}
+
+/// An argument which can't be converted into the type of its parameter.
+#[derive(Error, Debug, PartialEq)]
+#[error("invalid argument {}: {error}", .index + 1)]
+pub struct ArgumentError {
+    /// The 0-based index of the argument.
+    pub index: usize,
+    pub error: ConversionError,
+}
+
+/// Converts the argument at `index`, which must have been checked to exist by the arity.
+pub fn argument<T: FromValue>(arguments: &[Value], index: usize) -> Result<T, ArgumentError> {
+    T::from_value(&arguments[index]).map_err(|error| ArgumentError { index, error })
+}
+
+/// Wraps `object` into a Lox instance of its class.
+pub fn instance<T: LoxClass>(object: T) -> Value {
+    Value::Host(Rc::new(HostInstance(RefCell::new(object))))
+}
+
+/// An instance of a `LoxClass`, whose type is erased to be held by `Value`.
+pub trait HostObject: Any {
+    fn class_name(&self) -> &'static str;
+
+    /// Looks a property up, where fields shadow methods, and methods are bound to this.
+    fn get(self: Rc<Self>, name: &str) -> Option<Value>;
+
+    /// Sets a field, failing if `value` has another type, or returns `None` if it's not a field.
+    fn set(&self, name: &str, value: &Value) -> Option<Result<(), ConversionError>>;
+}
+
+struct HostInstance<T>(RefCell<T>);
+
+impl<T: LoxClass> HostObject for HostInstance<T> {
+    fn class_name(&self) -> &'static str {
+        T::NAME
+    }
+
+    fn get(self: Rc<Self>, name: &str) -> Option<Value> {
+        if let Some(value) = self.0.borrow().get(name) {
+            return Some(value);
+        }
+
+        let method = T::methods().iter().find(|m| m.name == name)?;
+        Some(Value::Callable(Rc::new(HostMethod { instance: self, method })))
+    }
+
+    fn set(&self, name: &str, value: &Value) -> Option<Result<(), ConversionError>> {
+        self.0.borrow_mut().set(name, value)
+    }
+}
+
+impl fmt::Debug for dyn HostObject {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostObject")
+            .field("class", &self.class_name())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for dyn HostObject {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instance", self.class_name())
+    }
+}
+
+/// A `LoxClass`, which creates an instance when called.
+pub struct HostClass<T>(PhantomData<T>);
+
+impl<T: LoxClass> HostClass<T> {
+    pub fn new() -> Self {
+        HostClass(PhantomData)
+    }
+}
+
+impl<T: LoxClass> Default for HostClass<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: LoxClass> Callable for HostClass<T> {
+    fn arity(&self) -> usize {
+        T::ARITY
+    }
+
+    fn call(
+        self: Rc<Self>,
+        _: &mut Interpreter,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        T::construct(&arguments)
+            .map(instance)
+            .map_err(|e| invalid_argument(paren, T::NAME, e))
+    }
+}
+
+impl<T: LoxClass> fmt::Debug for HostClass<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostClass")
+            .field("name", &T::NAME)
+            .finish()
+    }
+}
+
+impl<T: LoxClass> Display for HostClass<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(T::NAME)
+    }
+}
+
+/// A method of a `LoxClass` bound to an instance.
+struct HostMethod<T: 'static> {
+    instance: Rc<HostInstance<T>>,
+    method: &'static LoxMethod<T>,
+}
+
+impl<T: LoxClass> Callable for HostMethod<T> {
+    fn arity(&self) -> usize {
+        self.method.arity
+    }
+
+    fn call(
+        self: Rc<Self>,
+        _: &mut Interpreter,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let mut object = self.instance.0.borrow_mut();
+        (self.method.call)(&mut object, &arguments).map_err(|e| invalid_argument(paren, self.method.name, e))
+    }
+}
+
+impl<T: LoxClass> fmt::Debug for HostMethod<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostMethod")
+            .field("class", &T::NAME)
+            .field("name", &self.method.name)
+            .finish()
+    }
+}
+
+impl<T: LoxClass> Display for HostMethod<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.method.name)
+    }
+}
+
+fn invalid_argument(paren: &Token, function: &str, error: ArgumentError) -> RuntimeError {
+    RuntimeError::InvalidArgument {
+        snippet: Snippet::new(paren.range()),
+        function: function.to_string(),
+        position: error.index + 1,
+        error: error.error,
+    }
+}
+
+/// Finds the methods generated by `#[lox_methods]` for a concrete type, or no methods without it.
+/// `(&Methods::<T>::new()).methods()` resolves to `HasMethods` if it's implemented by `#[lox_methods]`,
+/// since method lookup tries the receiver `&Methods<T>` before auto-referencing it to `&&Methods<T>`.
+pub struct Methods<T>(PhantomData<T>);
+
+impl<T> Methods<T> {
+    pub fn new() -> Self {
+        Methods(PhantomData)
+    }
+}
+
+impl<T> Default for Methods<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub trait HasMethods<T> {
+    fn methods(&self) -> &'static [LoxMethod<T>];
+}
+
+pub trait NoMethods<T> {
+    fn methods(&self) -> &'static [LoxMethod<T>] {
+        &[]
+    }
+}
+
+impl<T> NoMethods<T> for &Methods<T> {}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use crate::{Error, LoxClass, lox_methods};
+    use crate::embed::Interpreter;
+    use crate::types::Number;
+    use super::*;
+
+    #[derive(LoxClass)]
+    struct Point {
+        x: Number,
+        y: Number,
+        #[lox(skip)]
+        moves: usize,
+    }
+
+    #[lox_methods]
+    impl Point {
+        fn norm(&self) -> Number {
+            self.x.hypot(self.y)
+        }
+
+        fn translate(&mut self, dx: Number, dy: Number) {
+            self.x += dx;
+            self.y += dy;
+            self.moves += 1;
+        }
+
+        fn moves(&self) -> i64 {
+            self.moves as i64
+        }
+
+        #[allow(dead_code)]
+        fn origin() -> Self {
+            Point { x: 0.0, y: 0.0, moves: 0 }
+        }
+    }
+
+    #[derive(LoxClass)]
+    struct Label {
+        text: String,
+    }
+
+    fn runtime_error(interpreter: &mut Interpreter, source: &str) -> RuntimeError {
+        match interpreter.eval(source) {
+            Err(Error::Runtime(error)) => error,
+            result => panic!("`{source}` must fail at runtime, but got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn lox_class() {
+        let mut interpreter = Interpreter::with_output(io::sink());
+        interpreter.define_class::<Point>();
+        interpreter.define_class::<Label>();
+        interpreter.define("label", Label { text: "a".to_string() });
+
+        assert_eq!(interpreter.eval("var p = Point(3, 4); p.norm();").unwrap(), Value::Number(5.0));
+        assert_eq!(interpreter.eval("p.translate(1, -1); p.x + p.y;").unwrap(), Value::Number(7.0));
+        assert_eq!(interpreter.eval("p.y = 0; p.moves();").unwrap(), Value::Number(1.0));
+        assert_eq!(interpreter.eval("p.norm();").unwrap(), Value::Number(4.0));
+        assert_eq!(interpreter.eval("label.text = label.text + 'b';").unwrap(), Value::String("ab".into()));
+        assert_eq!(interpreter.eval("label.text;").unwrap(), Value::String("ab".into()));
+
+        assert_eq!(interpreter.eval("Point;").unwrap().to_string(), "Point");
+        assert_eq!(interpreter.eval("p;").unwrap().to_string(), "Point instance");
+        assert_eq!(interpreter.eval("p.norm;").unwrap().to_string(), "<native fn norm>");
+        assert_eq!(interpreter.eval("p == p;").unwrap(), Value::Bool(true));
+        assert_eq!(interpreter.eval("p == Point(4, 0);").unwrap(), Value::Bool(false));
+    }
+
+    #[test]
+    fn lox_class_error() {
+        let mut interpreter = Interpreter::with_output(io::sink());
+        interpreter.define_class::<Point>();
+        interpreter.eval("var p = Point(3, 4);").unwrap();
+
+        let error = runtime_error(&mut interpreter, "Point(1, 'a');");
+        assert!(error.to_string().contains("invalid argument 2 of `Point`: expected number, found string"));
+        assert!(matches!(error, RuntimeError::InvalidArgument { position: 2, .. }));
+
+        let error = runtime_error(&mut interpreter, "p.translate(nil, 1);");
+        assert!(matches!(
+            error,
+            RuntimeError::InvalidArgument { ref function, position: 1, .. } if function == "translate",
+        ));
+
+        let error = runtime_error(&mut interpreter, "p.x = true;");
+        assert!(matches!(error, RuntimeError::InvalidProperty { ref name, .. } if name == "x"));
+        assert!(error.to_string().contains("invalid value of property `x`: expected number, found boolean"));
+
+        let error = runtime_error(&mut interpreter, "Point(1);");
+        assert!(matches!(error, RuntimeError::ArityMismatch { expected: 2, found: 1, .. }));
+        let error = runtime_error(&mut interpreter, "p.norm(1);");
+        assert!(matches!(error, RuntimeError::ArityMismatch { expected: 0, found: 1, .. }));
+
+        // Skipped fields and associated functions aren't exposed.
+        let error = runtime_error(&mut interpreter, "p.moves = 1;");
+        assert!(matches!(error, RuntimeError::UndefinedProperty { .. }));
+        let error = runtime_error(&mut interpreter, "p.origin;");
+        assert!(matches!(error, RuntimeError::UndefinedProperty { .. }));
+        assert_eq!(interpreter.eval("p.x;").unwrap(), Value::Number(3.0));
+    }
+}
@@ -0,0 +1,801 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::mem;
+use std::rc::Rc;
+use thiserror::Error;
+use qlox_macros::ResolveSnippet;
+use crate::ast::{
+    self, Accept, Assign, Binary, Block, Call, Depth, Expr, ExprVisitor, Expression, Get, Grouping, If,
+    Literal, LiteralValue, Logical, Print, Return, Set, Stmt, StmtVisitor, Super, This, Unary, Var, Variable, While,
+};
+use crate::consts::limit::MAX_CALL_DEPTH;
+use crate::convert::ConversionError;
+use crate::environment::Environment;
+use crate::gc::{GcConfig, Heap};
+use crate::src::Snippet;
+use crate::token::{Token, TokenKind};
+use crate::value::{Class, Function, Instance, Value};
+
+#[derive(Error, Debug, ResolveSnippet, PartialEq)]
+#[non_exhaustive]
+pub enum RuntimeError {
+    #[error("operand of `{operator}` must be a number, found {found}")]
+    NumberOperand {
+        snippet: Snippet,
+        operator: String,
+        found: &'static str,
+    },
+
+    #[error("operands of `{operator}` must be numbers, found {left} and {right}")]
+    NumberOperands {
+        snippet: Snippet,
+        operator: String,
+        left: &'static str,
+        right: &'static str,
+    },
+
+    #[error("operands of `+` must be two numbers or two strings, found {left} and {right}")]
+    AddOperands {
+        snippet: Snippet,
+        left: &'static str,
+        right: &'static str,
+    },
+
+    #[error("undefined variable `{name}`")]
+    UndefinedVariable {
+        snippet: Snippet,
+        name: String,
+    },
+
+    #[error("can only call functions and classes, found {found}")]
+    NotCallable {
+        snippet: Snippet,
+        found: &'static str,
+    },
+
+    #[error("expected {expected} arguments, found {found}")]
+    ArityMismatch {
+        snippet: Snippet,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error("stack overflow, too deep calls over {MAX_CALL_DEPTH}")]
+    StackOverflow {
+        snippet: Snippet,
+    },
+
+    #[error("only instances have properties, found {found}")]
+    NotInstance {
+        snippet: Snippet,
+        found: &'static str,
+    },
+
+    #[error("undefined property `{name}`")]
+    UndefinedProperty {
+        snippet: Snippet,
+        name: String,
+    },
+
+    #[error("superclass must be a class, found {found}")]
+    InvalidSuperclass {
+        snippet: Snippet,
+        found: String,
+    },
+
+    #[error("invalid argument {position} of `{function}`: {error}")]
+    InvalidArgument {
+        snippet: Snippet,
+        function: String,
+        position: usize,
+        error: ConversionError,
+    },
+
+    #[error("invalid value of property `{name}`: {error}")]
+    InvalidProperty {
+        snippet: Snippet,
+        name: String,
+        error: ConversionError,
+    },
+
+    #[error("native function `{name}` failed: {message}")]
+    Native {
+        snippet: Snippet,
+        name: String,
+        message: String,
+    },
+
+    /// Found only by the VM running a compiled Lox file whose bytecode passes verification,
+    /// but takes a value of the wrong type where the compiler always leaves one of the right type.
+    #[error("corrupt bytecode: {reason}")]
+    CorruptBytecode {
+        snippet: Snippet,
+        reason: String,
+    },
+}
+
+impl RuntimeError {
+    pub fn snippet(&self) -> &Snippet {
+        match self {
+            RuntimeError::NumberOperand { snippet, .. } |
+            RuntimeError::NumberOperands { snippet, .. } |
+            RuntimeError::AddOperands { snippet, .. } |
+            RuntimeError::UndefinedVariable { snippet, .. } |
+            RuntimeError::NotCallable { snippet, .. } |
+            RuntimeError::ArityMismatch { snippet, .. } |
+            RuntimeError::StackOverflow { snippet, .. } |
+            RuntimeError::NotInstance { snippet, .. } |
+            RuntimeError::UndefinedProperty { snippet, .. } |
+            RuntimeError::InvalidSuperclass { snippet, .. } |
+            RuntimeError::InvalidArgument { snippet, .. } |
+            RuntimeError::InvalidProperty { snippet, .. } |
+            RuntimeError::Native { snippet, .. } |
+            RuntimeError::CorruptBytecode { snippet, .. } => snippet,
+        }
+    }
+
+    fn number_operand(operator: &Token, operand: &Value) -> Self {
+        RuntimeError::NumberOperand {
+            snippet: Snippet::new(operator.range()),
+            operator: operator.lexeme.clone(),
+            found: operand.type_name(),
+        }
+    }
+
+    fn number_operands(operator: &Token, left: &Value, right: &Value) -> Self {
+        RuntimeError::NumberOperands {
+            snippet: Snippet::new(operator.range()),
+            operator: operator.lexeme.clone(),
+            left: left.type_name(),
+            right: right.type_name(),
+        }
+    }
+
+    fn undefined_property(name: &Token) -> Self {
+        RuntimeError::UndefinedProperty {
+            snippet: Snippet::new(name.range()),
+            name: name.lexeme.clone(),
+        }
+    }
+
+    fn not_instance(name: &Token, object: &Value) -> Self {
+        RuntimeError::NotInstance {
+            snippet: Snippet::new(name.range()),
+            found: object.type_name(),
+        }
+    }
+}
+
+/// The ways in which executing statements can stop before running to completion.
+enum Unwind {
+    Error(RuntimeError),
+    Return(Value),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    call_depth: usize,
+    heap: Heap,
+    out: Box<dyn Write>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+
+    /// Creates an interpreter whose `print` statements write to `out` instead of the standard output.
+    pub fn with_output(out: impl Write + 'static) -> Self {
+        Self::with_gc(out, GcConfig::default())
+    }
+
+    /// Creates an interpreter whose `print` statements write to `out`, and whose heap is collected as configured.
+    pub fn with_gc(out: impl Write + 'static, gc: GcConfig) -> Self {
+        let mut heap = Heap::new(gc);
+        let globals = heap.alloc_cell(Environment::new());
+        Interpreter {
+            globals: globals.clone(),
+            environment: globals,
+            call_depth: 0,
+            heap,
+            out: Box::new(out),
+        }
+    }
+
+    #[inline(always)]
+    pub fn heap(&mut self) -> &mut Heap {
+        &mut self.heap
+    }
+
+    pub fn interpret(&mut self, stmts: &[Stmt]) -> Result<(), RuntimeError> {
+        match self.execute_all(stmts) {
+            Ok(()) | Err(Unwind::Return(_)) => Ok(()),
+            Err(Unwind::Error(e)) => Err(e),
+        }
+    }
+
+    /// Executes `stmts`, and returns the value of the last one if it's an expression statement, or nil otherwise.
+    pub fn interpret_value(&mut self, stmts: &[Stmt]) -> Result<Value, RuntimeError> {
+        let (last, stmts) = match stmts.split_last() {
+            Some((Stmt::Expression(last), stmts)) => (Some(last), stmts),
+            _ => (None, stmts),
+        };
+        self.interpret(stmts)?;
+        last.map_or(Ok(Value::Nil), |last| self.evaluate(&last.expr))
+    }
+
+    /// Binds `name` to `value` in the global scope, redefining any global variable of the same name.
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        self.globals.borrow_mut().define(name, value);
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.borrow().get_own(name)
+    }
+
+    /// Executes the `body` of a function in `environment`, where its parameters are bound,
+    /// and returns its return value.
+    pub fn execute_body(&mut self, body: &[Stmt], environment: Environment) -> Result<Value, RuntimeError> {
+        let environment = self.heap.alloc_cell(environment);
+        match self.execute_block(body, environment) {
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(e)) => Err(e),
+        }
+    }
+
+    fn execute_all(&mut self, stmts: &[Stmt]) -> Result<(), Unwind> {
+        for stmt in stmts {
+            self.execute(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+        stmt.accept(self, ())
+    }
+
+    /// Executes `stmts` in `environment`, and then restores the current environment
+    /// whether they complete or not.
+    fn execute_block(
+        &mut self,
+        stmts: &[Stmt],
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<(), Unwind> {
+        let previous = mem::replace(&mut self.environment, environment);
+        let result = self.execute_all(stmts);
+        self.environment = previous;
+        result
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+        expr.accept(self, ())
+    }
+
+    /// Looks a variable up in the local scope at `depth`, or in the global scope if it's unresolved.
+    fn look_up_variable(&self, name: &Token, depth: &Depth) -> Result<Value, RuntimeError> {
+        match depth.get() {
+            Some(distance) => Environment::get_at(&self.environment, distance, name),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    fn evaluate_superclass(&self, superclass: &Variable) -> Result<Rc<Class>, RuntimeError> {
+        let value = self.look_up_variable(&superclass.name, &superclass.depth)?;
+        let class = match &value {
+            Value::Callable(callable) => (callable.clone() as Rc<dyn Any>).downcast::<Class>().ok(),
+            _ => None,
+        };
+        class.ok_or_else(|| RuntimeError::InvalidSuperclass {
+            snippet: Snippet::new(superclass.name.range()),
+            found: value.to_string(),
+        })
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExprVisitor<(), Result<Value, RuntimeError>> for &mut Interpreter {
+    fn visit_assign(self, expr: &Assign, _: ()) -> Result<Value, RuntimeError> {
+        let value = self.evaluate(&expr.value)?;
+        match expr.depth.get() {
+            Some(distance) => Environment::assign_at(&self.environment, distance, &expr.name, value.clone())?,
+            None => self.globals.borrow_mut().assign(&expr.name, value.clone())?,
+        }
+        Ok(value)
+    }
+
+    fn visit_binary(self, expr: &Binary, _: ()) -> Result<Value, RuntimeError> {
+        use TokenKind::*;
+        let left = self.evaluate(&expr.left)?;
+        let right = self.evaluate(&expr.right)?;
+        let operator = &expr.operator;
+
+        match (&operator.kind, &left, &right) {
+            (EqualEqual, _, _) => Ok(Value::Bool(left == right)),
+            (BangEqual, _, _) => Ok(Value::Bool(left != right)),
+            (Plus, Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
+            (Plus, Value::String(a), Value::String(b)) => Ok(Value::String(format!("{a}{b}").into())),
+            (Plus, _, _) => Err(RuntimeError::AddOperands {
+                snippet: Snippet::new(operator.range()),
+                left: left.type_name(),
+                right: right.type_name(),
+            }),
+            (Minus, Value::Number(a), Value::Number(b)) => Ok(Value::Number(a - b)),
+            (Star, Value::Number(a), Value::Number(b)) => Ok(Value::Number(a * b)),
+            (Slash, Value::Number(a), Value::Number(b)) => Ok(Value::Number(a / b)),
+            (Greater, Value::Number(a), Value::Number(b)) => Ok(Value::Bool(a > b)),
+            (GreaterEqual, Value::Number(a), Value::Number(b)) => Ok(Value::Bool(a >= b)),
+            (Less, Value::Number(a), Value::Number(b)) => Ok(Value::Bool(a < b)),
+            (LessEqual, Value::Number(a), Value::Number(b)) => Ok(Value::Bool(a <= b)),
+            (Minus | Star | Slash | Greater | GreaterEqual | Less | LessEqual, _, _) => {
+                Err(RuntimeError::number_operands(operator, &left, &right))
+            },
+            _ => unreachable!("invalid binary operator `{operator}`"),
+        }
+    }
+
+    fn visit_call(self, expr: &Call, _: ()) -> Result<Value, RuntimeError> {
+        let callee = self.evaluate(&expr.callee)?;
+        let arguments = expr.arguments
+            .iter()
+            .map(|a| self.evaluate(a))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let Value::Callable(callable) = callee else {
+            return Err(RuntimeError::NotCallable {
+                snippet: Snippet::new(expr.paren.range()),
+                found: callee.type_name(),
+            });
+        };
+
+        if arguments.len() != callable.arity() {
+            return Err(RuntimeError::ArityMismatch {
+                snippet: Snippet::new(expr.paren.range()),
+                expected: callable.arity(),
+                found: arguments.len(),
+            });
+        }
+
+        if self.call_depth == MAX_CALL_DEPTH {
+            return Err(RuntimeError::StackOverflow {
+                snippet: Snippet::new(expr.paren.range()),
+            });
+        }
+
+        self.call_depth += 1;
+        let result = callable.call(self, &expr.paren, arguments);
+        self.call_depth -= 1;
+        result
+    }
+
+    fn visit_get(self, expr: &Get, _: ()) -> Result<Value, RuntimeError> {
+        match self.evaluate(&expr.object)? {
+            Value::Instance(instance) => Instance::get(&instance, &expr.name, &mut self.heap),
+            Value::Host(object) => object.get(&expr.name.lexeme).ok_or_else(|| RuntimeError::undefined_property(&expr.name)),
+            object => Err(RuntimeError::not_instance(&expr.name, &object)),
+        }
+    }
+
+    fn visit_grouping(self, expr: &Grouping, _: ()) -> Result<Value, RuntimeError> {
+        self.evaluate(&expr.expr)
+    }
+
+    fn visit_literal(self, expr: &Literal, _: ()) -> Result<Value, RuntimeError> {
+        Ok(match &expr.value {
+            LiteralValue::Number(n) => Value::Number(*n),
+            LiteralValue::String(s) => Value::String(s.as_str().into()),
+            LiteralValue::True => Value::Bool(true),
+            LiteralValue::False => Value::Bool(false),
+            LiteralValue::Nil => Value::Nil,
+        })
+    }
+
+    fn visit_logical(self, expr: &Logical, _: ()) -> Result<Value, RuntimeError> {
+        let left = self.evaluate(&expr.left)?;
+        let short_circuits = match expr.operator.kind {
+            TokenKind::Or => left.is_truthy(),
+            TokenKind::And => !left.is_truthy(),
+            _ => unreachable!("invalid logical operator `{}`", expr.operator),
+        };
+
+        if short_circuits {
+            Ok(left)
+        } else {
+            self.evaluate(&expr.right)
+        }
+    }
+
+    fn visit_set(self, expr: &Set, _: ()) -> Result<Value, RuntimeError> {
+        let object = match self.evaluate(&expr.object)? {
+            object @ (Value::Instance(_) | Value::Host(_)) => object,
+            object => return Err(RuntimeError::not_instance(&expr.name, &object)),
+        };
+
+        let value = self.evaluate(&expr.value)?;
+        match object {
+            Value::Instance(instance) => instance.borrow_mut().set(&expr.name, value.clone()),
+            Value::Host(object) => match object.set(&expr.name.lexeme, &value) {
+                Some(result) => result.map_err(|error| RuntimeError::InvalidProperty {
+                    snippet: Snippet::new(expr.name.range()),
+                    name: expr.name.lexeme.clone(),
+                    error,
+                })?,
+                None => return Err(RuntimeError::undefined_property(&expr.name)),
+            },
+            _ => unreachable!("object must have been checked to be an instance"),
+        }
+        Ok(value)
+    }
+
+    fn visit_super(self, expr: &Super, _: ()) -> Result<Value, RuntimeError> {
+        let superclass = self.evaluate_superclass(&Variable {
+            name: expr.keyword.clone(),
+            depth: expr.depth.clone(),
+        })?;
+
+        // `this` is always bound in the scope just inside the one where `super` is bound.
+        let distance = expr.depth.get().expect("`super` must be resolved in a subclass");
+        let this = Environment::ancestor(&self.environment, distance - 1).borrow().get_own("this");
+        let Some(Value::Instance(instance)) = this else {
+            unreachable!("`this` must be bound to an instance in a method");
+        };
+
+        match superclass.find_method(&expr.method.lexeme) {
+            Some(method) => Ok(Value::Callable(method.bind(instance, &mut self.heap))),
+            None => Err(RuntimeError::UndefinedProperty {
+                snippet: Snippet::new(expr.method.range()),
+                name: expr.method.lexeme.clone(),
+            }),
+        }
+    }
+
+    fn visit_this(self, expr: &This, _: ()) -> Result<Value, RuntimeError> {
+        self.look_up_variable(&expr.keyword, &expr.depth)
+    }
+
+    fn visit_unary(self, expr: &Unary, _: ()) -> Result<Value, RuntimeError> {
+        let right = self.evaluate(&expr.right)?;
+        match (&expr.operator.kind, &right) {
+            (TokenKind::Bang, _) => Ok(Value::Bool(!right.is_truthy())),
+            (TokenKind::Minus, Value::Number(n)) => Ok(Value::Number(-n)),
+            (TokenKind::Minus, _) => Err(RuntimeError::number_operand(&expr.operator, &right)),
+            (TokenKind::InterpolationStart, Value::String(_)) => Ok(right),
+            (TokenKind::InterpolationStart, _) => Ok(Value::String(right.to_string().into())),
+            _ => unreachable!("invalid unary operator `{}`", expr.operator),
+        }
+    }
+
+    fn visit_variable(self, expr: &Variable, _: ()) -> Result<Value, RuntimeError> {
+        self.look_up_variable(&expr.name, &expr.depth)
+    }
+}
+
+impl StmtVisitor<(), Result<(), Unwind>> for &mut Interpreter {
+    fn visit_block(self, stmt: &Block, _: ()) -> Result<(), Unwind> {
+        let environment = self.heap.alloc_cell(Environment::with_enclosing(self.environment.clone()));
+        self.execute_block(&stmt.stmts, environment)
+    }
+
+    fn visit_class(self, stmt: &ast::Class, _: ()) -> Result<(), Unwind> {
+        let superclass = stmt.superclass
+            .as_ref()
+            .map(|s| self.evaluate_superclass(s))
+            .transpose()?;
+        self.environment.borrow_mut().define(&stmt.name.lexeme, Value::Nil);
+
+        // Methods of a subclass close over an extra scope where `super` is bound to its superclass.
+        let enclosing = superclass.as_ref().map(|superclass| {
+            let mut environment = Environment::with_enclosing(self.environment.clone());
+            environment.define("super", Value::Callable(superclass.clone()));
+            let environment = self.heap.alloc_cell(environment);
+            mem::replace(&mut self.environment, environment)
+        });
+
+        let methods = stmt.methods
+            .iter()
+            .map(|method| {
+                let is_initializer = method.name.lexeme == "init";
+                let function = Function::new(method.clone(), self.environment.clone(), is_initializer);
+                (method.name.lexeme.clone(), self.heap.alloc(function))
+            })
+            .collect();
+
+        if let Some(enclosing) = enclosing {
+            self.environment = enclosing;
+        }
+
+        let class = self.heap.alloc(Class::new(stmt.name.lexeme.clone(), superclass, methods));
+        self.environment.borrow_mut().assign(&stmt.name, Value::Callable(class))?;
+        Ok(())
+    }
+
+    fn visit_expression(self, stmt: &Expression, _: ()) -> Result<(), Unwind> {
+        self.evaluate(&stmt.expr)?;
+        Ok(())
+    }
+
+    fn visit_function(self, stmt: &ast::Function, _: ()) -> Result<(), Unwind> {
+        let function = self.heap.alloc(Function::new(stmt.clone(), self.environment.clone(), false));
+        self.environment.borrow_mut().define(&stmt.name.lexeme, Value::Callable(function));
+        Ok(())
+    }
+
+    fn visit_if(self, stmt: &If, _: ()) -> Result<(), Unwind> {
+        if self.evaluate(&stmt.condition)?.is_truthy() {
+            self.execute(&stmt.then_branch)
+        } else if let Some(else_branch) = &stmt.else_branch {
+            self.execute(else_branch)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_print(self, stmt: &Print, _: ()) -> Result<(), Unwind> {
+        let value = self.evaluate(&stmt.expr)?;
+        // A closed output must not abort the program, just like a closed terminal.
+        let _ = writeln!(self.out, "{value}");
+        Ok(())
+    }
+
+    fn visit_return(self, stmt: &Return, _: ()) -> Result<(), Unwind> {
+        let value = match &stmt.value {
+            Some(value) => self.evaluate(value)?,
+            None => Value::Nil,
+        };
+        Err(Unwind::Return(value))
+    }
+
+    fn visit_var(self, stmt: &Var, _: ()) -> Result<(), Unwind> {
+        let value = match &stmt.initializer {
+            Some(initializer) => self.evaluate(initializer)?,
+            None => Value::Nil,
+        };
+        self.environment.borrow_mut().define(&stmt.name.lexeme, value);
+        Ok(())
+    }
+
+    fn visit_while(self, stmt: &While, _: ()) -> Result<(), Unwind> {
+        while self.evaluate(&stmt.condition)?.is_truthy() {
+            self.execute(&stmt.body)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::*;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+
+    /// A shared buffer to capture the output of `print` statements.
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+        let stmts = Parser::new(&tokens).parse().unwrap();
+        Resolver::new().resolve(&stmts).unwrap();
+        stmts
+    }
+
+    /// Interprets `source`, and checks that it behaves the same when collecting garbage on every allocation.
+    fn interpret(source: &str) -> Result<String, RuntimeError> {
+        let stmts = parse(source);
+        let run = |gc| {
+            let output = Output::default();
+            Interpreter::with_gc(output.clone(), gc).interpret(&stmts)?;
+            let bytes = output.0.borrow().clone();
+            Ok(String::from_utf8(bytes).unwrap())
+        };
+
+        let result = run(GcConfig::default());
+        assert_eq!(run(GcConfig { stress: true, ..GcConfig::default() }), result, "{source} under GC stress");
+        result
+    }
+
+    #[test]
+    fn interpreter_interpret() {
+        let cases = [
+            ("print 1 + 2 * 3;", "7\n"),
+            ("print (1 + 2) * 3 / 2;", "4.5\n"),
+            ("print -(1 - 3);", "2\n"),
+            ("print 'ab' + \"cd\";", "abcd\n"),
+            ("var a = 'b'; print 'a${a}c${1 + 2}d${ 'e${a}f' }';", "abc3debf\n"),
+            ("print 1 < 2; print 2 <= 1; print 3 > 3; print 3 >= 3;", "true\nfalse\nfalse\ntrue\n"),
+            ("print !nil; print !0; print !'';", "true\nfalse\nfalse\n"),
+            ("print nil == nil; print nil == false; print 1 == 1; print 'a' != 'a';", "true\nfalse\ntrue\nfalse\n"),
+            ("print 1 == '1'; print true == true;", "false\ntrue\n"),
+            ("print nil or 'a'; print 0 or 'b'; print nil and 'c'; print 1 and 'd';", "a\n0\nnil\nd\n"),
+            ("if (nil) print 1; else print 2; if (0) print 3;", "2\n3\n"),
+            ("{ print 1; { print 2; } }", "1\n2\n"),
+            ("var a; print a; var a = 1; print a; a = a + 1; print a;", "nil\n1\n2\n"),
+            ("var a = 1; var b; print b = a = 3; print a;", "3\n3\n"),
+            ("var a = 'g'; { var a = 'l'; print a; a = 'l2'; print a; } print a;", "l\nl2\ng\n"),
+            ("var a = 1; { a = 2; var b = a; { print b; } } print a;", "2\n2\n"),
+            ("var i = 0; while (i < 3) { print i; i = i + 1; }", "0\n1\n2\n"),
+            ("for (var i = 0; i < 3; i = i + 1) print i;", "0\n1\n2\n"),
+            ("fun f(a, b) { print a + b; } print f; print f(1, 2);", "<fn f>\n3\nnil\n"),
+            ("fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } print fib(15);", "610\n"),
+            ("fun f() { while (true) { return 'early'; } print 'unreachable'; } print f();", "early\n"),
+            (r#"
+                fun counter() {
+                    var i = 0;
+                    fun count() { i = i + 1; return i; }
+                    return count;
+                }
+                var a = counter(); var b = counter();
+                print a(); print a(); print b();
+            "#, "1\n2\n1\n"),
+            ("var f = 1; fun g() { return f; } print g() == 1; var h = g; print h == g;", "true\ntrue\n"),
+            (r#"
+                var a = 'global';
+                {
+                    fun show() { print a; }
+                    show();
+                    var a = 'block';
+                    show();
+                    print a;
+                }
+            "#, "global\nglobal\nblock\n"),
+            ("class A { m() { return 'm'; } } print A; var a = A(); print a; print a.m();", "A\nA instance\nm\n"),
+            ("class A {} var a = A(); a.f = 1; a.f = a.f + 1; print a.f;", "2\n"),
+            (r#"
+                class Point {
+                    init(x, y) { this.x = x; this.y = y; }
+                    sum() { return this.x + this.y; }
+                }
+                var p = Point(1, 2);
+                var sum = p.sum;
+                p.x = 10;
+                print sum();
+                print p.init(3, 4) == p;
+                print p.sum();
+            "#, "12\ntrue\n7\n"),
+            ("class A { init() { this.a = 1; return; this.a = 2; } } print A().a;", "1\n"),
+            ("class A { m() { return 1; } } var a = A(); fun f() { return 2; } a.m = f; print a.m();", "2\n"),
+            (r#"
+                class A {
+                    init(name) { this.name = name; }
+                    hello() { return 'hello ' + this.name; }
+                    who() { return 'A'; }
+                }
+                class B < A {
+                    init(name) { super.init(name + '!'); }
+                    who() { return 'B < ' + super.who(); }
+                }
+                class C < B {}
+                var c = C('c');
+                print c.hello(); print c.who();
+            "#, "hello c!\nB < A\n"),
+        ];
+
+        for (source, expected) in cases {
+            assert_eq!(interpret(source).unwrap(), expected, "{source}");
+        }
+    }
+
+    #[test]
+    fn interpreter_interpret_collect() {
+        let source = r#"
+            class Node {
+                init() { this.me = this; }
+                closure() { fun f() { return this; } this.f = f; }
+            }
+            fun counter() {
+                var i = 0;
+                fun count() { i = i + 1; return i; }
+                return count;
+            }
+            for (var i = 0; i < 10; i = i + 1) {
+                Node().closure();
+                counter();
+            }
+            var node = Node();
+        "#;
+        let mut interpreter = Interpreter::with_output(Output::default());
+        interpreter.interpret(&parse(source)).unwrap();
+        // The globals, `Node` with its 2 methods, `counter`, and `node`.
+        assert_eq!(interpreter.heap().collect(), 6);
+    }
+
+    #[test]
+    fn interpreter_interpret_error() {
+        assert_eq!(interpret("print -'abc';").err().unwrap(), RuntimeError::NumberOperand {
+            snippet: Snippet::new(6..7),
+            operator: "-".to_string(),
+            found: "string",
+        });
+
+        assert_eq!(interpret("print 1 < nil;").err().unwrap(), RuntimeError::NumberOperands {
+            snippet: Snippet::new(8..9),
+            operator: "<".to_string(),
+            left: "number",
+            right: "nil",
+        });
+
+        assert_eq!(interpret("print 'a' + 1;").err().unwrap(), RuntimeError::AddOperands {
+            snippet: Snippet::new(10..11),
+            left: "string",
+            right: "number",
+        });
+
+        assert_eq!(interpret("{ var a = 1; } print a;").err().unwrap(), RuntimeError::UndefinedVariable {
+            snippet: Snippet::new(21..22),
+            name: "a".to_string(),
+        });
+
+        assert_eq!(interpret("var a; { b = a; }").err().unwrap(), RuntimeError::UndefinedVariable {
+            snippet: Snippet::new(9..10),
+            name: "b".to_string(),
+        });
+
+        assert_eq!(interpret("'f'(1);").err().unwrap(), RuntimeError::NotCallable {
+            snippet: Snippet::new(5..6),
+            found: "string",
+        });
+
+        assert_eq!(interpret("fun f(a) {} f(1, 2);").err().unwrap(), RuntimeError::ArityMismatch {
+            snippet: Snippet::new(18..19),
+            expected: 1,
+            found: 2,
+        });
+
+        assert_eq!(interpret("fun f() { f(); } f();").err().unwrap(), RuntimeError::StackOverflow {
+            snippet: Snippet::new(12..13),
+        });
+
+        assert_eq!(interpret("class A {} A().b;").err().unwrap(), RuntimeError::UndefinedProperty {
+            snippet: Snippet::new(15..16),
+            name: "b".to_string(),
+        });
+
+        assert_eq!(interpret("var a = 1; a.b = 2;").err().unwrap(), RuntimeError::NotInstance {
+            snippet: Snippet::new(13..14),
+            found: "number",
+        });
+
+        assert_eq!(interpret("fun A() {} class B < A {}").err().unwrap(), RuntimeError::InvalidSuperclass {
+            snippet: Snippet::new(21..22),
+            found: "<fn A>".to_string(),
+        });
+
+        assert_eq!(interpret("class A { init(a) {} } A();").err().unwrap(), RuntimeError::ArityMismatch {
+            snippet: Snippet::new(25..26),
+            expected: 1,
+            found: 0,
+        });
+
+        let source = "class A {} class B < A { m() { return super.m(); } } B().m();";
+        assert_eq!(interpret(source).err().unwrap(), RuntimeError::UndefinedProperty {
+            snippet: Snippet::new(44..45),
+            name: "m".to_string(),
+        });
+    }
+}
@@ -5,26 +5,222 @@ mod src;
 mod token;
 mod scanner;
 mod ast;
+mod parser;
+mod gc;
+mod value;
+mod environment;
+mod resolver;
+mod interpreter;
+mod vm;
+mod convert;
+mod host;
+mod embed;
+mod report;
+#[cfg(feature = "ffi")]
+mod ffi;
 
 use std::{fs, io, result};
-use std::io::Write;
-use clap::Parser;
+use std::path::Path;
+use std::rc::Rc;
+use std::io::{IsTerminal, Write};
 use text_colorizer::Colorize;
 use thiserror::Error;
+use crate::ast::Stmt;
 use crate::consts::exitcode;
+use crate::consts::gc::DEFAULT_THRESHOLD;
 use crate::consts::tag::ERROR;
+use crate::gc::GcConfig;
+use crate::report::{Diagnostic, Renderer, ToDiagnostic};
+use crate::parser::Parser;
+use crate::resolver::Resolver;
 use crate::scanner::Scanner;
-use crate::src::SnippetResolver;
+use crate::src::{FileId, SnippetResolver, SourceMap};
+use crate::vm::Vm;
+use crate::vm::compiler::Compiler;
+use crate::vm::disassembler::Disassembler;
+use crate::vm::loxc::{self, Loxc};
+use crate::vm::value::Function;
+
+pub use crate::convert::{ConversionError, FromValue, IntoValue};
+pub use crate::embed::{Interpreter, Program};
+pub use crate::host::{ArgumentError, LoxClass, LoxMethod};
+pub use crate::interpreter::RuntimeError;
+pub use crate::value::Value;
+pub use qlox_macros::{LoxClass, include_lox, lox, lox_methods};
+
+/// Scanning Lox source into tokens, which is the first stage of running it.
+///
+/// ```
+/// use qlox::scanning::{Scanner, TokenKind};
+///
+/// let tokens = Scanner::new(b"print x + 1;").scan_tokens().unwrap();
+/// let kinds = tokens.iter().map(|t| t.kind.clone()).collect::<Vec<_>>();
+/// assert_eq!(kinds, [
+///     TokenKind::Print,
+///     TokenKind::Identifier("x".to_string()),
+///     TokenKind::Plus,
+///     TokenKind::Number(1.0),
+///     TokenKind::Semicolon,
+///     TokenKind::Eof,
+/// ]);
+/// assert_eq!((tokens[1].lexeme.as_str(), tokens[1].offset), ("x", 6));
+/// ```
+pub mod scanning {
+    pub use crate::scanner::{Error, Scanner};
+    pub use crate::token::{Token, TokenKind};
+}
+
+/// Parsing tokens into a syntax tree of statements.
+///
+/// ```
+/// use qlox::parsing::Parser;
+/// use qlox::scanning::Scanner;
+///
+/// let tokens = Scanner::new(b"print 1 + 2 * 3;").scan_tokens().unwrap();
+/// let stmts = Parser::new(&tokens).parse().unwrap();
+/// assert_eq!(stmts[0].to_string(), "(print (+ 1 (* 2 3)))");
+///
+/// let tokens = Scanner::new(b"print 1 +;").scan_tokens().unwrap();
+/// let errors = Parser::new(&tokens).parse().unwrap_err();
+/// assert_eq!(errors[0].snippet().offset(), 9);
+/// ```
+pub mod parsing {
+    pub use crate::parser::{Error, Parser};
+}
+
+/// Resolving the local variables of a syntax tree, which fills in the scopes they're found in,
+/// and checks the rules the parser can't, such as `return` outside of a function.
+///
+/// ```
+/// use qlox::parsing::Parser;
+/// use qlox::resolving::{Error, Resolver};
+/// use qlox::scanning::Scanner;
+///
+/// let tokens = Scanner::new(b"{ var a = 1; { var a = a; } }").scan_tokens().unwrap();
+/// let stmts = Parser::new(&tokens).parse().unwrap();
+/// let errors = Resolver::new().resolve(&stmts).unwrap_err();
+/// assert!(matches!(&errors[0], Error::ReadInOwnInitializer { name, .. } if name == "a"));
+/// ```
+pub mod resolving {
+    pub use crate::resolver::{Error, Resolver};
+}
+
+/// The syntax tree of Lox code, which is walked by implementing `ExprVisitor` and `StmtVisitor`,
+/// and calling `Accept::accept` on its nodes.
+///
+/// ```
+/// use qlox::parsing::Parser;
+/// use qlox::scanning::Scanner;
+/// use qlox::syntax::*;
+///
+/// /// Counts the calls in an expression.
+/// struct CallCounter;
+///
+/// impl ExprVisitor<(), usize> for &CallCounter {
+///     fn visit_assign(self, expr: &Assign, _: ()) -> usize { expr.value.accept(self, ()) }
+///     fn visit_binary(self, expr: &Binary, _: ()) -> usize {
+///         expr.left.accept(self, ()) + expr.right.accept(self, ())
+///     }
+///     fn visit_call(self, expr: &Call, _: ()) -> usize {
+///         1 + expr.callee.accept(self, ()) + expr.arguments.iter().map(|a| a.accept(self, ())).sum::<usize>()
+///     }
+///     fn visit_get(self, expr: &Get, _: ()) -> usize { expr.object.accept(self, ()) }
+///     fn visit_grouping(self, expr: &Grouping, _: ()) -> usize { expr.expr.accept(self, ()) }
+///     fn visit_literal(self, _: &Literal, _: ()) -> usize { 0 }
+///     fn visit_logical(self, expr: &Logical, _: ()) -> usize {
+///         expr.left.accept(self, ()) + expr.right.accept(self, ())
+///     }
+///     fn visit_set(self, expr: &Set, _: ()) -> usize {
+///         expr.object.accept(self, ()) + expr.value.accept(self, ())
+///     }
+///     fn visit_super(self, _: &Super, _: ()) -> usize { 0 }
+///     fn visit_this(self, _: &This, _: ()) -> usize { 0 }
+///     fn visit_unary(self, expr: &Unary, _: ()) -> usize { expr.right.accept(self, ()) }
+///     fn visit_variable(self, _: &Variable, _: ()) -> usize { 0 }
+/// }
+///
+/// let tokens = Scanner::new(b"f(g(1), 2) + h();").scan_tokens().unwrap();
+/// let stmts = Parser::new(&tokens).parse().unwrap();
+/// let Stmt::Expression(Expression { expr }) = &stmts[0] else { unreachable!() };
+/// assert_eq!(expr.accept(&CallCounter, ()), 3);
+/// ```
+pub mod syntax {
+    pub use crate::ast::*;
+}
+
+/// Diagnostics of errors found in Lox code, which are rendered as colored text, plain text or JSON.
+///
+/// Each source is added to a `SourceMap`, which gives it a `FileId` and a range of offsets of its own.
+/// Errors are created with snippets holding only an offset in the source map, and `SnippetResolver` resolves them
+/// against the source map to find their files and positions before they're converted into diagnostics by `ToDiagnostic`.
+///
+/// ```
+/// use qlox::diagnostic::{Location, Renderer, SnippetResolver, SourceMap, ToDiagnostic};
+/// use qlox::scanning::Scanner;
+///
+/// let mut sources = SourceMap::new();
+/// sources.add("a.lox", b"print 1;".as_slice());
+/// let id = sources.add("b.lox", b"var a = 1;\nprint a @ 2;".as_slice());
+/// let file = sources.file(id);
+/// let errors = Scanner::with_offset(file.source(), file.start()).scan_tokens().unwrap_err();
+/// let errors = SnippetResolver::new(&sources).resolve(errors);
+/// let diagnostic = errors[0].to_diagnostic();
+/// assert_eq!((diagnostic.code, diagnostic.message.as_str()), ("E0001", "unexpected char `@`"));
+/// let rendered = Renderer::Plain.render(&[diagnostic]);
+/// assert!(rendered.contains("--> b.lox:2:9"));
+/// assert!(rendered.contains("2 | print a @ 2;"));
+///
+/// let mut location = Location::new(errors[0].snippet().file_offset().unwrap());
+/// location.resolve(file.source());
+/// assert_eq!(location.to_string(), "line 2:9");
+/// ```
+pub mod diagnostic {
+    pub use crate::report::{Diagnostic, Label, Renderer, Severity, ToDiagnostic};
+    pub use crate::src::{
+        FileId, Index, Location, Position, ResolveSnippet, Snippet, SnippetResolver, SourceFile, SourceMap,
+    };
+}
+
+// Lets the code generated by `qlox_macros` refer to this crate as `::qlox` within it, too.
+extern crate self as qlox;
+
+/// The items used by the code generated by `qlox_macros`, which aren't part of the API.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::host::{argument, instance, HasMethods, Methods, NoMethods};
+
+    pub mod ast {
+        pub use crate::ast::*;
+        pub use crate::token::{Token, TokenKind};
+    }
+}
 
 pub type Result<T> = result::Result<T, Error>;
 
+/// An error running Lox code, which is displayed as its diagnostics rendered in plain text.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
-    #[error("{ERROR}: {0}\n")]
+    #[error("{}", Renderer::Plain.render(&self.diagnostics()))]
     Io(#[from] io::Error),
 
-    #[error("{}", .0.iter().map(|e| format!("{e}\n")).collect::<String>())]
+    #[error("{}", Renderer::Plain.render(&self.diagnostics()))]
     Scanner(Vec<scanner::Error>),
+
+    #[error("{}", Renderer::Plain.render(&self.diagnostics()))]
+    Parser(Vec<parser::Error>),
+
+    #[error("{}", Renderer::Plain.render(&self.diagnostics()))]
+    Resolver(Vec<resolver::Error>),
+
+    #[error("{}", Renderer::Plain.render(&self.diagnostics()))]
+    Compiler(Vec<vm::compiler::Error>),
+
+    #[error("{}", Renderer::Plain.render(&self.diagnostics()))]
+    Runtime(interpreter::RuntimeError),
+
+    #[error("{}", Renderer::Plain.render(&self.diagnostics()))]
+    Loxc(loxc::Error),
 }
 
 impl Error {
@@ -33,26 +229,135 @@ impl Error {
         match self {
             Io(_) => exitcode::IO_ERR,
             Scanner(_) => exitcode::SCANNER_ERR,
+            Parser(_) => exitcode::PARSER_ERR,
+            Resolver(_) => exitcode::RESOLVER_ERR,
+            Compiler(_) => exitcode::COMPILER_ERR,
+            Runtime(_) => exitcode::RUNTIME_ERR,
+            Loxc(_) => exitcode::LOXC_ERR,
+        }
+    }
+
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        use Error::*;
+        match self {
+            Io(e) => vec![e.to_diagnostic()],
+            Scanner(errors) => errors.iter().map(ToDiagnostic::to_diagnostic).collect(),
+            Parser(errors) => errors.iter().map(ToDiagnostic::to_diagnostic).collect(),
+            Resolver(errors) => errors.iter().map(ToDiagnostic::to_diagnostic).collect(),
+            Compiler(errors) => errors.iter().map(ToDiagnostic::to_diagnostic).collect(),
+            Runtime(e) => vec![e.to_diagnostic()],
+            Loxc(e) => vec![e.to_diagnostic()],
         }
     }
 }
 
-/// A tree-walk interpreter for the Lox programming language
-#[derive(Parser, Debug)]
-#[command(name = Lox::name(), version = Lox::version(), author, about)]
+/// An interpreter for the Lox programming language
+#[derive(clap::Parser, Debug)]
+#[command(name = Lox::name(), version = Lox::version(), author, about, args_conflicts_with_subcommands = true)]
 pub struct Args {
-    /// A Lox file to run
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// A Lox file, or a compiled Lox file (.loxc), to run
     #[arg(value_name = "FILE")]
     pub filename: Option<String>,
+
+    /// The backend to run Lox code on
+    #[arg(long, value_enum, default_value_t = Backend::Tree)]
+    pub backend: Backend,
+
+    /// Print the bytecode compiled from Lox code before running it
+    #[arg(long)]
+    pub disassemble: bool,
+
+    /// The number of objects allocated before the first garbage collection, after which
+    /// garbage is collected whenever the heap grows to twice the objects surviving the last collection
+    #[arg(long, value_name = "OBJECTS", default_value_t = DEFAULT_THRESHOLD)]
+    pub gc_threshold: usize,
+
+    /// Collect garbage on every allocation, which is slow but makes GC bugs show up early
+    #[arg(long)]
+    pub gc_stress: bool,
+
+    /// The format to print errors in
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Human, global = true)]
+    pub error_format: ErrorFormat,
+}
+
+impl Args {
+    /// Returns the Lox file to run or compile, which errors are reported in.
+    pub fn source_file(&self) -> Option<&str> {
+        match &self.command {
+            Some(Command::Compile { filename, .. }) => Some(filename),
+            None => self.filename.as_deref(),
+        }
+    }
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum Command {
+    /// Compile a Lox file into bytecode, which can be run later without scanning and parsing it again
+    Compile {
+        /// A Lox file to compile
+        #[arg(value_name = "FILE")]
+        filename: String,
+
+        /// The compiled Lox file to write [default: FILE with the extension .loxc]
+        #[arg(short, long, value_name = "OUTPUT")]
+        output: Option<String>,
+    },
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// A tree-walk interpreter over the syntax tree
+    Tree,
+    /// A bytecode compiler and a stack-based virtual machine
+    Vm,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    /// Errors with the lines of code they're found at, colored on terminals
+    Human,
+    /// One line for each error, with its file and position
+    Short,
+    /// One JSON object per line for each error, for tools to parse
+    Json,
+}
+
+/// Renders human errors in color only when stderr, where they're printed, is a terminal.
+impl From<ErrorFormat> for Renderer {
+    fn from(format: ErrorFormat) -> Self {
+        match format {
+            ErrorFormat::Human if io::stderr().is_terminal() => Renderer::Colored,
+            ErrorFormat::Human => Renderer::Plain,
+            ErrorFormat::Short => Renderer::Short,
+            ErrorFormat::Json => Renderer::Json,
+        }
+    }
 }
 
 pub struct Lox {
     args: Args,
+    interpreter: interpreter::Interpreter,
+    vm: Vm,
+    /// The sources run so far, which are kept to show the code of errors in functions declared in them.
+    sources: SourceMap,
 }
 
 impl Lox {
     pub fn new(args: Args) -> Self {
-        Lox { args }
+        let gc = GcConfig {
+            threshold: args.gc_threshold,
+            stress: args.gc_stress,
+        };
+        Lox {
+            args,
+            interpreter: interpreter::Interpreter::with_gc(io::stdout(), gc),
+            vm: Vm::with_gc(io::stdout(), gc),
+            sources: SourceMap::new(),
+        }
     }
 
     pub fn name() -> &'static str {
@@ -63,48 +368,158 @@ impl Lox {
         option_env!("CARGO_PKG_VERSION").unwrap_or("undefined")
     }
 
-    pub fn start(&self) -> Result<()> {
-        match self.args.filename {
-            Some(ref filename) => self.run_file(filename),
+    pub fn start(&mut self) -> Result<()> {
+        if let Some(Command::Compile { filename, output }) = &self.args.command {
+            return Self::compile_file(filename, output.as_deref());
+        }
+        match self.args.filename.clone() {
+            Some(filename) => self.run_file(&filename),
             None => self.run_prompt(),
         }
     }
 
-    fn run_file(&self, path: &str) -> Result<()> {
-        self.run(fs::read(path)?)
+    /// Runs a Lox file, or a compiled Lox file, which is detected by its extension or its content.
+    fn run_file(&mut self, path: &str) -> Result<()> {
+        let bytes = fs::read(path)?;
+        if path.ends_with(".loxc") || bytes.starts_with(loxc::MAGIC) {
+            self.run_loxc(path, &bytes)
+        } else {
+            self.run(path, bytes)
+        }
+    }
+
+    /// Compiles the Lox file at `path` into a compiled Lox file at `output`,
+    /// which defaults to `path` with the extension `.loxc`.
+    fn compile_file(path: &str, output: Option<&str>) -> Result<()> {
+        // The offsets of a compiled Lox file are in its source alone, which starts at offset 0 in a new source map.
+        let mut sources = SourceMap::new();
+        let id = sources.add(path, fs::read(path)?);
+        let stmts = Self::parse(&sources, id)?;
+        let function = Self::compile(&stmts, &sources)?;
+
+        let output = output.map_or_else(|| Path::new(path).with_extension("loxc"), |o| Path::new(o).to_path_buf());
+        fs::write(output, Loxc::new(sources.file(id).source(), function).to_bytes())?;
+        Ok(())
     }
 
-    fn run_prompt(&self) -> Result<()> {
+    /// Runs a compiled Lox file on the VM. Runtime errors are shown with snippets
+    /// only if the Lox file it's compiled from is found next to it, and unchanged.
+    fn run_loxc(&mut self, path: &str, bytes: &[u8]) -> Result<()> {
+        let loxc = Loxc::from_bytes(bytes).map_err(Error::Loxc)?;
+        let source_path = Path::new(path).with_extension("lox");
+        let mut sources = SourceMap::new();
+        if let Ok(source) = fs::read(&source_path)
+            && !source.is_empty() && loxc.is_compiled_from(&source) {
+            sources.add(source_path.to_string_lossy(), source);
+        }
+
+        if self.args.disassemble {
+            print!("{}", Disassembler::new(&sources, &loxc.function));
+        }
+
+        self.vm
+            .interpret(loxc.function)
+            .map_err(|e| match e {
+                RuntimeError::CorruptBytecode { reason, .. } => Error::Loxc(loxc::Error::Corrupt { reason }),
+                e => Error::Runtime(SnippetResolver::new(&sources).resolve(e)),
+            })
+    }
+
+    fn run_prompt(&mut self) -> Result<()> {
         println!("Welcome to `{} {}` REPL.", Lox::name().blue(), Lox::version().blue());
-        println!("Type `{}`, `{}`, or `{}` in order to issue a command.",
-                 "version".blue(), "clear".blue(), "exit".blue());
+        println!("Type `{}`, `{}`, `{}`, or `{}` in order to issue a command.",
+                 "version".blue(), "clear".blue(), "disassemble <code>".blue(), "exit".blue());
 
+        let mut entry = 0;
         loop {
             print!(">>> ");
             io::stdout().flush()?;
             let mut line = String::new();
-            let _ = io::stdin().read_line(&mut line)?;
+            if io::stdin().read_line(&mut line)? == 0 {
+                println!();
+                return Ok(());
+            }
             match line.trim() {
                 "version" => println!("{} {}", Lox::name(), Lox::version()),
                 "clear" => clearscreen::clear().unwrap_or_else(|e| {
                     eprintln!("{ERROR}: {e}");
                 }),
                 "exit" => return Ok(()),
-                _ => self.run(line.into_bytes()).unwrap_or_else(|e| {
-                    eprint!("{e}");
-                }),
+                trimmed => {
+                    // Each entry is a file of its own, which errors are reported in.
+                    entry += 1;
+                    let name = format!("<repl:{entry}>");
+                    let result = match trimmed.strip_prefix("disassemble ") {
+                        Some(code) => self.disassemble(&name, code.as_bytes().to_vec()),
+                        None => self.run(&name, line.into_bytes()),
+                    };
+                    result.unwrap_or_else(|e| {
+                        eprint!("{}", Renderer::from(self.args.error_format).render(&e.diagnostics()));
+                    });
+                },
             }
         }
     }
 
-    fn run(&self, source: Vec<u8>) -> Result<()> {
-        let snippet_resolver = SnippetResolver::new(&source);
+    /// Runs `source` named `name`, which is added to the sources run so far.
+    fn run(&mut self, name: &str, source: Vec<u8>) -> Result<()> {
+        let id = self.sources.add(name, source);
+        let stmts = Self::parse(&self.sources, id)?;
+        if self.args.backend == Backend::Tree && !self.args.disassemble {
+            return self.interpreter
+                .interpret(&stmts)
+                .map_err(|e| SnippetResolver::new(&self.sources).resolve(e))
+                .map_err(Error::Runtime);
+        }
+
+        let function = Self::compile(&stmts, &self.sources)?;
+        if self.args.disassemble {
+            print!("{}", Disassembler::new(&self.sources, &function));
+        }
 
-        let _tokens = Scanner::new(&source)
+        match self.args.backend {
+            Backend::Tree => self.interpreter.interpret(&stmts),
+            Backend::Vm => self.vm.interpret(function),
+        }
+        .map_err(|e| SnippetResolver::new(&self.sources).resolve(e))
+        .map_err(Error::Runtime)
+    }
+
+    /// Prints the bytecode compiled from `source` named `name` without running it.
+    fn disassemble(&mut self, name: &str, source: Vec<u8>) -> Result<()> {
+        let id = self.sources.add(name, source);
+        let stmts = Self::parse(&self.sources, id)?;
+        let function = Self::compile(&stmts, &self.sources)?;
+        print!("{}", Disassembler::new(&self.sources, &function));
+        Ok(())
+    }
+
+    /// Scans, parses and resolves the file `id` of `sources` into statements ready to run.
+    fn parse(sources: &SourceMap, id: FileId) -> Result<Vec<Stmt>> {
+        let snippet_resolver = SnippetResolver::new(sources);
+        let file = sources.file(id);
+        let tokens = Scanner::with_offset(file.source(), file.start())
             .scan_tokens()
             .map_err(|e| snippet_resolver.resolve(e))
             .map_err(Error::Scanner)?;
 
-        Ok(())
+        let stmts = Parser::new(&tokens)
+            .parse()
+            .map_err(|e| snippet_resolver.resolve(e))
+            .map_err(Error::Parser)?;
+
+        Resolver::new()
+            .resolve(&stmts)
+            .map_err(|e| snippet_resolver.resolve(e))
+            .map_err(Error::Resolver)?;
+
+        Ok(stmts)
+    }
+
+    fn compile(stmts: &[Stmt], sources: &SourceMap) -> Result<Rc<Function>> {
+        Compiler::new()
+            .compile(stmts)
+            .map_err(|e| SnippetResolver::new(sources).resolve(e))
+            .map_err(Error::Compiler)
     }
 }
\ No newline at end of file
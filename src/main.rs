@@ -1,10 +1,21 @@
 use std::process;
 use clap::Parser;
 use qlox::{Args, Lox};
+use qlox::diagnostic::Renderer;
 
 fn main() {
-    if let Err(e) = Lox::new(Args::parse()).start() {
-        eprint!("{e}");
+    let args = Args::parse();
+    let renderer = Renderer::from(args.error_format);
+    let file = args.source_file().map(str::to_string);
+    if let Err(e) = Lox::new(args).start() {
+        let diagnostics = e.diagnostics()
+            .into_iter()
+            .map(|d| match &file {
+                Some(file) => d.with_file(file),
+                None => d,
+            })
+            .collect::<Vec<_>>();
+        eprint!("{}", renderer.render(&diagnostics));
         process::exit(e.exit_code());
     }
 }
@@ -0,0 +1,754 @@
+use std::mem;
+use thiserror::Error;
+#[cfg(not(qlox_macros))]
+use qlox_macros::ResolveSnippet;
+use crate::ast::{
+    Assign, Binary, Block, Call, Class, Depth, Expr, Expression, Function, Get, Grouping, If,
+    Literal, LiteralValue, Logical, Print, Return, Set, Stmt, Super, This, Unary, Var, Variable, While,
+};
+use crate::consts::limit::MAX_ARGUMENTS;
+use crate::src::{Index, Snippet};
+use crate::token::{Token, TokenKind};
+
+#[derive(Error, Debug, PartialEq)]
+#[cfg_attr(not(qlox_macros), derive(ResolveSnippet))]
+#[non_exhaustive]
+pub enum Error {
+    #[error("expected expression")]
+    ExpectedExpression {
+        snippet: Snippet,
+    },
+
+    #[error("expected {expected}")]
+    ExpectedToken {
+        snippet: Snippet,
+        expected: &'static str,
+    },
+
+    #[error("invalid assignment target")]
+    InvalidAssignmentTarget {
+        snippet: Snippet,
+    },
+
+    #[error("can't have more than {MAX_ARGUMENTS} arguments")]
+    TooManyArguments {
+        snippet: Snippet,
+    },
+
+    #[error("can't have more than {MAX_ARGUMENTS} parameters")]
+    TooManyParameters {
+        snippet: Snippet,
+    },
+}
+
+impl Error {
+    pub fn snippet(&self) -> &Snippet {
+        match self {
+            Error::ExpectedExpression { snippet, .. } |
+            Error::ExpectedToken { snippet, .. } |
+            Error::InvalidAssignmentTarget { snippet, .. } |
+            Error::TooManyArguments { snippet, .. } |
+            Error::TooManyParameters { snippet, .. } => snippet,
+        }
+    }
+}
+
+pub struct Parser<'a> {
+    tokens: &'a [Token],
+    next: Index,
+    errors: Vec<Error>,
+}
+
+impl<'a> Parser<'a> {
+    /// Creates a parser over `tokens`, which must be terminated by a `TokenKind::Eof` token
+    /// as returned by `Scanner::scan_tokens`.
+    pub fn new(tokens: &'a [Token]) -> Self {
+        Parser {
+            tokens,
+            next: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<Error>> {
+        let mut stmts = Vec::new();
+        while !self.is_at_end() {
+            if let Some(stmt) = self.declaration() {
+                stmts.push(stmt);
+            }
+        }
+
+        if !self.errors.is_empty() {
+            return Err(mem::take(&mut self.errors));
+        }
+        Ok(stmts)
+    }
+
+    /// Parses a declaration, or records its error and skips to the next statement boundary
+    /// so that parsing can go on and report every syntax error at once.
+    fn declaration(&mut self) -> Option<Stmt> {
+        match self.try_declaration() {
+            Ok(stmt) => Some(stmt),
+            Err(e) => {
+                self.errors.push(e);
+                self.synchronize();
+                None
+            },
+        }
+    }
+
+    fn synchronize(&mut self) {
+        use TokenKind::*;
+        while !self.is_at_end() {
+            if self.advance().kind == Semicolon { return; }
+            if let Class | Fun | Var | For | If | While | Print | Return = self.peek().kind { return; }
+        }
+    }
+
+    fn try_declaration(&mut self) -> Result<Stmt, Error> {
+        if self.matches(&[TokenKind::Class]).is_some() {
+            self.class_declaration()
+        } else if self.matches(&[TokenKind::Fun]).is_some() {
+            self.function("function").map(Stmt::Function)
+        } else if self.matches(&[TokenKind::Var]).is_some() {
+            self.var_declaration()
+        } else {
+            self.statement()
+        }
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt, Error> {
+        let name = self.consume_identifier("class name")?;
+        let superclass = if self.matches(&[TokenKind::Less]).is_some() {
+            Some(Variable {
+                name: self.consume_identifier("superclass name")?,
+                depth: Depth::default(),
+            })
+        } else {
+            None
+        };
+
+        self.consume(TokenKind::LeftBrace, "`{` before class body")?;
+        let mut methods = Vec::new();
+        while !self.check(&TokenKind::RightBrace) && !self.is_at_end() {
+            methods.push(self.function("method")?);
+        }
+        self.consume(TokenKind::RightBrace, "`}` after class body")?;
+
+        Ok(Stmt::Class(Class { name, superclass, methods }))
+    }
+
+    fn function(&mut self, kind: &'static str) -> Result<Function, Error> {
+        let name = self.consume_identifier(kind)?;
+        self.consume(TokenKind::LeftParen, "`(` after function name")?;
+        let mut params = Vec::new();
+        if !self.check(&TokenKind::RightParen) {
+            loop {
+                if params.len() == MAX_ARGUMENTS {
+                    self.errors.push(Error::TooManyParameters {
+                        snippet: Snippet::new(self.peek().range()),
+                    });
+                }
+                params.push(self.consume_identifier("parameter name")?);
+                if self.matches(&[TokenKind::Comma]).is_none() { break; }
+            }
+        }
+        self.consume(TokenKind::RightParen, "`)` after parameters")?;
+
+        self.consume(TokenKind::LeftBrace, "`{` before function body")?;
+        let body = self.block()?.into();
+        Ok(Function { name, params, body })
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt, Error> {
+        let name = self.consume_identifier("variable name")?;
+        let initializer = if self.matches(&[TokenKind::Equal]).is_some() {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(TokenKind::Semicolon, "`;` after variable declaration")?;
+        Ok(Stmt::Var(Var { name, initializer }))
+    }
+
+    fn statement(&mut self) -> Result<Stmt, Error> {
+        use TokenKind::*;
+        match self.peek().kind {
+            For => {
+                self.go_next();
+                self.for_statement()
+            },
+            If => {
+                self.go_next();
+                self.if_statement()
+            },
+            Print => {
+                self.go_next();
+                self.print_statement()
+            },
+            Return => {
+                let keyword = self.advance();
+                self.return_statement(keyword)
+            },
+            While => {
+                self.go_next();
+                self.while_statement()
+            },
+            LeftBrace => {
+                self.go_next();
+                Ok(Stmt::Block(Block { stmts: self.block()? }))
+            },
+            _ => self.expression_statement(),
+        }
+    }
+
+    /// Desugars a `for` loop into a `while` loop wrapped in blocks for its initializer and increment.
+    fn for_statement(&mut self) -> Result<Stmt, Error> {
+        self.consume(TokenKind::LeftParen, "`(` after `for`")?;
+        let initializer = if self.matches(&[TokenKind::Semicolon]).is_some() {
+            None
+        } else if self.matches(&[TokenKind::Var]).is_some() {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if self.check(&TokenKind::Semicolon) {
+            Expr::Literal(Literal {
+                value: LiteralValue::True,
+                token: self.peek().clone(),
+            })
+        } else {
+            self.expression()?
+        };
+        self.consume(TokenKind::Semicolon, "`;` after loop condition")?;
+
+        let increment = if self.check(&TokenKind::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenKind::RightParen, "`)` after for clauses")?;
+
+        let mut body = self.statement()?;
+        if let Some(increment) = increment {
+            body = Stmt::Block(Block {
+                stmts: vec![body, Stmt::Expression(Expression { expr: increment })],
+            });
+        }
+        body = Stmt::While(While { condition, body: Box::new(body) });
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(Block { stmts: vec![initializer, body] });
+        }
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt, Error> {
+        self.consume(TokenKind::LeftParen, "`(` after `if`")?;
+        let condition = self.expression()?;
+        self.consume(TokenKind::RightParen, "`)` after if condition")?;
+
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.matches(&[TokenKind::Else]).is_some() {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+        Ok(Stmt::If(If { condition, then_branch, else_branch }))
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt, Error> {
+        let expr = self.expression()?;
+        self.consume(TokenKind::Semicolon, "`;` after value")?;
+        Ok(Stmt::Print(Print { expr }))
+    }
+
+    fn return_statement(&mut self, keyword: Token) -> Result<Stmt, Error> {
+        let value = if self.check(&TokenKind::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenKind::Semicolon, "`;` after return value")?;
+        Ok(Stmt::Return(Return { keyword, value }))
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, Error> {
+        self.consume(TokenKind::LeftParen, "`(` after `while`")?;
+        let condition = self.expression()?;
+        self.consume(TokenKind::RightParen, "`)` after while condition")?;
+        let body = Box::new(self.statement()?);
+        Ok(Stmt::While(While { condition, body }))
+    }
+
+    /// Parses the declarations of a block whose `{` has already been consumed.
+    fn block(&mut self) -> Result<Vec<Stmt>, Error> {
+        let mut stmts = Vec::new();
+        while !self.check(&TokenKind::RightBrace) && !self.is_at_end() {
+            if let Some(stmt) = self.declaration() {
+                stmts.push(stmt);
+            }
+        }
+        self.consume(TokenKind::RightBrace, "`}` after block")?;
+        Ok(stmts)
+    }
+
+    fn expression_statement(&mut self) -> Result<Stmt, Error> {
+        let expr = self.expression()?;
+        self.consume(TokenKind::Semicolon, "`;` after expression")?;
+        Ok(Stmt::Expression(Expression { expr }))
+    }
+
+    fn expression(&mut self) -> Result<Expr, Error> {
+        self.assignment()
+    }
+
+    fn assignment(&mut self) -> Result<Expr, Error> {
+        let expr = self.or()?;
+        if let Some(equal) = self.matches(&[TokenKind::Equal]) {
+            // Assignment is right-associative, and its target is only known to be an l-value
+            // once the `=` is found. An invalid target doesn't confuse the parser, so it's
+            // reported without synchronizing.
+            let value = self.assignment()?;
+            return match expr {
+                Expr::Variable(Variable { name, .. }) => Ok(Expr::Assign(Assign {
+                    name,
+                    value: Box::new(value),
+                    depth: Depth::default(),
+                })),
+                Expr::Get(Get { object, name }) => Ok(Expr::Set(Set {
+                    object,
+                    name,
+                    value: Box::new(value),
+                })),
+                _ => {
+                    self.errors.push(Error::InvalidAssignmentTarget {
+                        snippet: Snippet::new(equal.range()),
+                    });
+                    Ok(expr)
+                },
+            };
+        }
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr, Error> {
+        self.logical(Self::and, TokenKind::Or)
+    }
+
+    fn and(&mut self) -> Result<Expr, Error> {
+        self.logical(Self::equality, TokenKind::And)
+    }
+
+    /// Parses a left-associative chain of `operand (operator operand)*` for a short-circuit operator.
+    fn logical(
+        &mut self,
+        operand: fn(&mut Self) -> Result<Expr, Error>,
+        operator: TokenKind,
+    ) -> Result<Expr, Error> {
+        let mut expr = operand(self)?;
+        while let Some(operator) = self.matches(std::slice::from_ref(&operator)) {
+            let right = operand(self)?;
+            expr = Expr::Logical(Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            });
+        }
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> Result<Expr, Error> {
+        use TokenKind::*;
+        self.binary(Self::comparison, &[BangEqual, EqualEqual])
+    }
+
+    fn comparison(&mut self) -> Result<Expr, Error> {
+        use TokenKind::*;
+        self.binary(Self::term, &[Greater, GreaterEqual, Less, LessEqual])
+    }
+
+    fn term(&mut self) -> Result<Expr, Error> {
+        use TokenKind::*;
+        self.binary(Self::factor, &[Minus, Plus])
+    }
+
+    fn factor(&mut self) -> Result<Expr, Error> {
+        use TokenKind::*;
+        self.binary(Self::unary, &[Slash, Star])
+    }
+
+    /// Parses a left-associative chain of `operand (operator operand)*`.
+    fn binary(
+        &mut self,
+        operand: fn(&mut Self) -> Result<Expr, Error>,
+        operators: &[TokenKind],
+    ) -> Result<Expr, Error> {
+        let mut expr = operand(self)?;
+        while let Some(operator) = self.matches(operators) {
+            let right = operand(self)?;
+            expr = Expr::Binary(Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            });
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        if let Some(operator) = self.matches(&[TokenKind::Bang, TokenKind::Minus]) {
+            let right = self.unary()?;
+            return Ok(Expr::Unary(Unary {
+                operator,
+                right: Box::new(right),
+            }));
+        }
+        self.call()
+    }
+
+    fn call(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.matches(&[TokenKind::LeftParen]).is_some() {
+                expr = self.finish_call(expr)?;
+            } else if self.matches(&[TokenKind::Dot]).is_some() {
+                let name = self.consume_identifier("property name after `.`")?;
+                expr = Expr::Get(Get {
+                    object: Box::new(expr),
+                    name,
+                });
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, Error> {
+        let mut arguments = Vec::new();
+        if !self.check(&TokenKind::RightParen) {
+            loop {
+                if arguments.len() == MAX_ARGUMENTS {
+                    self.errors.push(Error::TooManyArguments {
+                        snippet: Snippet::new(self.peek().range()),
+                    });
+                }
+                arguments.push(self.expression()?);
+                if self.matches(&[TokenKind::Comma]).is_none() { break; }
+            }
+        }
+        let paren = self.consume(TokenKind::RightParen, "`)` after arguments")?;
+
+        Ok(Expr::Call(Call {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        }))
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        let value = match &self.peek().kind {
+            TokenKind::False => LiteralValue::False,
+            TokenKind::True => LiteralValue::True,
+            TokenKind::Nil => LiteralValue::Nil,
+            TokenKind::Number(n) => LiteralValue::Number(*n),
+            TokenKind::String(s) => LiteralValue::String(s.clone()),
+            TokenKind::StringPart(_) => return self.interpolation(),
+            TokenKind::Identifier(_) => {
+                return Ok(Expr::Variable(Variable {
+                    name: self.advance(),
+                    depth: Depth::default(),
+                }));
+            },
+            TokenKind::This => {
+                return Ok(Expr::This(This {
+                    keyword: self.advance(),
+                    depth: Depth::default(),
+                }));
+            },
+            TokenKind::Super => {
+                let keyword = self.advance();
+                self.consume(TokenKind::Dot, "`.` after `super`")?;
+                let method = self.consume_identifier("superclass method name")?;
+                return Ok(Expr::Super(Super {
+                    keyword,
+                    method,
+                    depth: Depth::default(),
+                }));
+            },
+            TokenKind::LeftParen => {
+                self.go_next();
+                let expr = self.expression()?;
+                self.consume(TokenKind::RightParen, "`)` after expression")?;
+                return Ok(Expr::Grouping(Grouping {
+                    expr: Box::new(expr),
+                }));
+            },
+            _ => {
+                return Err(Error::ExpectedExpression {
+                    snippet: Snippet::new(self.peek().range()),
+                });
+            },
+        };
+        Ok(Expr::Literal(Literal {
+            value,
+            token: self.advance(),
+        }))
+    }
+
+    /// Parses a string holding interpolations into the concatenation of its parts, where each interpolated
+    /// expression is stringified by a unary `${` operator, and the empty parts between them are left out.
+    fn interpolation(&mut self) -> Result<Expr, Error> {
+        let mut parts = Vec::new();
+        loop {
+            // The scanner puts a string part before and after every interpolation.
+            let token = self.advance();
+            if let TokenKind::StringPart(s) = &token.kind && !s.is_empty() {
+                parts.push((token.offset, Expr::Literal(Literal {
+                    value: LiteralValue::String(s.clone()),
+                    token,
+                })));
+            }
+
+            let Some(operator) = self.matches(&[TokenKind::InterpolationStart]) else { break };
+            let expr = self.expression()?;
+            self.consume(TokenKind::InterpolationEnd, "`}` after interpolation")?;
+            parts.push((operator.offset, Expr::Unary(Unary {
+                operator,
+                right: Box::new(expr),
+            })));
+        }
+
+        // The parts are joined by `+`s of no code, found at the parts they add.
+        let mut parts = parts.into_iter();
+        // Tokens not made by the scanner may hold only an empty string part, which leaves nothing to join.
+        let Some((_, first)) = parts.next() else {
+            return Err(Error::ExpectedToken {
+                snippet: Snippet::new(self.peek().range()),
+                expected: "`${` in interpolated string",
+            });
+        };
+        Ok(parts.fold(first, |left, (offset, right)| {
+            Expr::Binary(Binary {
+                left: Box::new(left),
+                operator: Token {
+                    kind: TokenKind::Plus,
+                    lexeme: String::new(),
+                    offset,
+                },
+                right: Box::new(right),
+            })
+        }))
+    }
+
+    fn consume(&mut self, kind: TokenKind, expected: &'static str) -> Result<Token, Error> {
+        if self.check(&kind) {
+            Ok(self.advance())
+        } else {
+            Err(Error::ExpectedToken {
+                snippet: Snippet::new(self.peek().range()),
+                expected,
+            })
+        }
+    }
+
+    fn consume_identifier(&mut self, expected: &'static str) -> Result<Token, Error> {
+        if let TokenKind::Identifier(_) = self.peek().kind {
+            Ok(self.advance())
+        } else {
+            Err(Error::ExpectedToken {
+                snippet: Snippet::new(self.peek().range()),
+                expected,
+            })
+        }
+    }
+
+    #[inline(always)]
+    fn matches(&mut self, kinds: &[TokenKind]) -> Option<Token> {
+        if kinds.iter().any(|k| self.check(k)) {
+            Some(self.advance())
+        } else {
+            None
+        }
+    }
+
+    #[inline(always)]
+    fn check(&self, kind: &TokenKind) -> bool {
+        self.peek().kind == *kind
+    }
+
+    #[inline(always)]
+    fn advance(&mut self) -> Token {
+        let token = self.peek().clone();
+        self.go_next();
+        token
+    }
+
+    #[inline(always)]
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next]
+    }
+
+    #[inline(always)]
+    fn is_at_end(&self) -> bool {
+        self.peek().kind == TokenKind::Eof
+    }
+
+    #[inline(always)]
+    fn go_next(&mut self) {
+        if !self.is_at_end() {
+            self.next += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn scan(source: &str) -> Vec<Token> {
+        Scanner::new(source.as_bytes()).scan_tokens().unwrap()
+    }
+
+    fn parse(source: &str) -> Result<Vec<Stmt>, Vec<Error>> {
+        Parser::new(&scan(source)).parse()
+    }
+
+    #[test]
+    fn parser_parse() {
+        let cases = [
+            ("print 1 + 2 * 3;", "(print (+ 1 (* 2 3)))"),
+            ("1 - 2 - 3;", "(; (- (- 1 2) 3))"),
+            ("8 / 4 / 2;", "(; (/ (/ 8 4) 2))"),
+            ("(1 + 2) * 3;", "(; (* (group (+ 1 2)) 3))"),
+            ("-1 < 2 == !false;", "(; (== (< (- 1) 2) (! false)))"),
+            ("1 >= 2 != 3 <= 4;", "(; (!= (>= 1 2) (<= 3 4)))"),
+            ("!!true;", "(; (! (! true)))"),
+            ("'a' + nil;", "(; (+ a nil))"),
+            ("true or false and nil;", "(; (or true (and false nil)))"),
+            ("var a; var b = 1;", "(var a) (var b 1)"),
+            ("{ print 1; { print 2; } }", "(block (print 1) (block (print 2)))"),
+            ("if (true) print 1; else if (false) print 2;", "(if true (print 1) (if false (print 2)))"),
+            ("while (true) print 1;", "(while true (print 1))"),
+            ("for (;;) print 1;", "(while true (print 1))"),
+            ("for (var i = 0; false; 1) print 1;", "(block (var i 0) (while false (block (print 1) (; 1))))"),
+            ("fun f(a, b) { return 1; return; }", "(fun f (a b) (return 1) (return))"),
+            ("class A < B { m() {} n(a) { print a; } }", "(class A < B (fun m ()) (fun n (a) (print a)))"),
+            ("a = b = c or d;", "(; (= a (= b (or c d))))"),
+            ("f(1)(a, b)();", "(; (call (call (call f 1) a b)))"),
+            ("-f() * g(h());", "(; (* (- (call f)) (call g (call h))))"),
+            ("a.b(c).d = this.e = super.f();", "(; (= (. (call (. a b) c) d) (= (. this e) (call (. super f)))))"),
+            ("print 'a${b}c${d + 1}';", "(print (+ (+ (+ a (${ b)) c) (${ (+ d 1))))"),
+            ("print '${a}${'b${c}'}';", "(print (+ (${ a) (${ (+ b (${ c)))))"),
+        ];
+
+        for (source, expected) in cases {
+            let stmts = parse(source).unwrap();
+            let actual = stmts.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(" ");
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn parser_parse_error() {
+        let cases = [
+            ("1 +;", Error::ExpectedExpression {
+                snippet: Snippet::new(3..4),
+            }),
+            ("(1 + 2;", Error::ExpectedToken {
+                snippet: Snippet::new(6..7),
+                expected: "`)` after expression",
+            }),
+            ("print 1", Error::ExpectedToken {
+                snippet: Snippet::new(7..7),
+                expected: "`;` after value",
+            }),
+            ("var 1;", Error::ExpectedToken {
+                snippet: Snippet::new(4..5),
+                expected: "variable name",
+            }),
+            ("{ print 1;", Error::ExpectedToken {
+                snippet: Snippet::new(10..10),
+                expected: "`}` after block",
+            }),
+            ("a + b = c;", Error::InvalidAssignmentTarget {
+                snippet: Snippet::new(6..7),
+            }),
+            ("super;", Error::ExpectedToken {
+                snippet: Snippet::new(5..6),
+                expected: "`.` after `super`",
+            }),
+            ("a.1;", Error::ExpectedToken {
+                snippet: Snippet::new(2..3),
+                expected: "property name after `.`",
+            }),
+            ("f(1, 2;", Error::ExpectedToken {
+                snippet: Snippet::new(6..7),
+                expected: "`)` after arguments",
+            }),
+            ("'a${1 2}';", Error::ExpectedToken {
+                snippet: Snippet::new(6..7),
+                expected: "`}` after interpolation",
+            }),
+        ];
+
+        for (source, expected) in cases {
+            assert_eq!(parse(source).err().unwrap(), vec![expected]);
+        }
+
+        // A string part which isn't followed by an interpolation, as the scanner never makes it.
+        let mut tokens = scan("print '';");
+        tokens[1].kind = TokenKind::StringPart(String::new());
+        assert_eq!(Parser::new(&tokens).parse().err().unwrap(), vec![Error::ExpectedToken {
+            snippet: Snippet::new(8..9),
+            expected: "`${` in interpolated string",
+        }]);
+    }
+
+    #[test]
+    fn parser_parse_error_recovery() {
+        let source = "print 1 print 2; var = 3; { 4 + ; print 5; } fun f( { } class C { 6 } print 7;";
+        let errors = parse(source).err().unwrap();
+        assert_eq!(errors, vec![
+            Error::ExpectedToken {
+                snippet: Snippet::new(8..13),
+                expected: "`;` after value",
+            },
+            Error::ExpectedToken {
+                snippet: Snippet::new(21..22),
+                expected: "variable name",
+            },
+            Error::ExpectedExpression {
+                snippet: Snippet::new(32..33),
+            },
+            Error::ExpectedToken {
+                snippet: Snippet::new(52..53),
+                expected: "parameter name",
+            },
+            Error::ExpectedToken {
+                snippet: Snippet::new(66..67),
+                expected: "method",
+            },
+        ]);
+    }
+
+    #[test]
+    fn parser_parse_error_too_many() {
+        let names = (0..=MAX_ARGUMENTS).map(|i| format!("a{i}")).collect::<Vec<_>>().join(", ");
+        let source = format!("f({names}); fun f({names}) {{}}");
+        let errors = parse(&source).err().unwrap();
+        let name = format!("a{MAX_ARGUMENTS}");
+        let offset = source.find(&name).unwrap();
+        let param_offset = source.rfind(&name).unwrap();
+        assert_eq!(errors, vec![
+            Error::TooManyArguments {
+                snippet: Snippet::new(offset..offset + name.len()),
+            },
+            Error::TooManyParameters {
+                snippet: Snippet::new(param_offset..param_offset + name.len()),
+            },
+        ]);
+    }
+}
@@ -0,0 +1,661 @@
+use std::fmt::Write;
+use std::io;
+use serde_json::json;
+use text_colorizer::Colorize;
+use unicode_width::UnicodeWidthChar;
+use crate::interpreter::RuntimeError;
+use crate::src::{ResolveSnippet, Snippet, SourceMap};
+use crate::vm::{compiler, loxc};
+use crate::{parser, resolver, scanner};
+
+/// The most lines of a snippet rendered in full, beyond which only its first 2 lines and its last line are rendered.
+const MAX_SNIPPET_LINES: usize = 4;
+
+/// The number of columns a tab is expanded to, on both the lines of code and the markers under them.
+const TAB_WIDTH: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+}
+
+/// A secondary span of a diagnostic, which points at code related to the error with a message of its own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Snippet,
+    pub message: String,
+}
+
+/// An error, or another message, found in Lox code, which is rendered separately by a `Renderer`.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    /// The path of the file the diagnostic is found in, if it's found in a file.
+    pub file: Option<String>,
+    pub severity: Severity,
+    /// The code identifying the kind of the diagnostic, such as `E0001`.
+    pub code: &'static str,
+    pub message: String,
+    /// The code the diagnostic is found at, if it's found in Lox code.
+    pub span: Option<Snippet>,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic {
+            file: None,
+            severity,
+            code,
+            message: message.into(),
+            span: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    #[inline(always)]
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, code, message)
+    }
+
+    pub fn with_file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Returns the name of the file the span is found in, or the file the diagnostic is found in if it has no span.
+    pub fn file_name(&self) -> Option<&str> {
+        self.span.as_ref().and_then(Snippet::file_name).or(self.file.as_deref())
+    }
+
+    pub fn with_span(mut self, span: Snippet) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_label(mut self, span: Snippet, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into() });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+}
+
+impl ResolveSnippet for Diagnostic {
+    fn resolve_snippet(&mut self, sources: &SourceMap) {
+        if let Some(span) = &mut self.span {
+            span.resolve(sources);
+        }
+        for label in &mut self.labels {
+            label.span.resolve(sources);
+        }
+    }
+}
+
+/// Converts an error into a diagnostic, giving it an error code, and notes and help text if any.
+pub trait ToDiagnostic {
+    fn to_diagnostic(&self) -> Diagnostic;
+}
+
+impl ToDiagnostic for scanner::Error {
+    fn to_diagnostic(&self) -> Diagnostic {
+        use scanner::Error::*;
+        let code = match self {
+            UnexpectedChar { .. } => "E0001",
+            InvalidUtf8Char { .. } => "E0002",
+            UnterminatedMultiLineComment { .. } => "E0003",
+            UnterminatedSingleQuoteString { .. } => "E0004",
+            UnterminatedDoubleQuoteString { .. } => "E0005",
+            InvalidEscape { .. } => "E0006",
+            UnterminatedInterpolation { .. } => "E0007",
+            UnterminatedRawString { .. } => "E0008",
+            UnterminatedMultiLineString { .. } => "E0009",
+            InterpolationInMultiLineString { .. } => "E0010",
+        };
+        let diagnostic = Diagnostic::error(code, self.to_string()).with_span(self.snippet().clone());
+        match self {
+            InvalidUtf8Char { .. } => diagnostic.with_note("Lox source must be encoded in UTF-8"),
+            UnterminatedMultiLineComment { .. } => diagnostic.with_help("close the comment with `*/`"),
+            UnterminatedSingleQuoteString { .. } => diagnostic.with_help("close the string with `'`"),
+            UnterminatedDoubleQuoteString { .. } => diagnostic.with_help("close the string with `\"`"),
+            InvalidEscape { .. } => diagnostic
+                .with_note(r#"valid escapes are `\n`, `\t`, `\r`, `\\`, `\'`, `\"`, `\$`, `\0`, `\xNN` and `\u{XXXX}`"#)
+                .with_help(r"escape a backslash with `\\` to keep it in the string"),
+            UnterminatedInterpolation { .. } => diagnostic.with_help("close the interpolation with `}`"),
+            UnterminatedRawString { hashes, .. } => {
+                diagnostic.with_help(format!("close the string with `\"{}`", "#".repeat(*hashes)))
+            },
+            UnterminatedMultiLineString { quote, .. } => {
+                diagnostic.with_help(format!("close the string with `{quote}{quote}{quote}`"))
+            },
+            InterpolationInMultiLineString { .. } => diagnostic
+                .with_note("multi-line strings aren't interpolated")
+                .with_help(r"escape the `$` with `\$` to keep `${` in the string, or concatenate the value"),
+            _ => diagnostic,
+        }
+    }
+}
+
+impl ToDiagnostic for parser::Error {
+    fn to_diagnostic(&self) -> Diagnostic {
+        use parser::Error::*;
+        let code = match self {
+            ExpectedExpression { .. } => "E0101",
+            ExpectedToken { .. } => "E0102",
+            InvalidAssignmentTarget { .. } => "E0103",
+            TooManyArguments { .. } => "E0104",
+            TooManyParameters { .. } => "E0105",
+        };
+        let diagnostic = Diagnostic::error(code, self.to_string()).with_span(self.snippet().clone());
+        match self {
+            InvalidAssignmentTarget { .. } => diagnostic.with_help("only variables and properties can be assigned to"),
+            _ => diagnostic,
+        }
+    }
+}
+
+impl ToDiagnostic for resolver::Error {
+    fn to_diagnostic(&self) -> Diagnostic {
+        use resolver::Error::*;
+        let code = match self {
+            ReadInOwnInitializer { .. } => "E0201",
+            DuplicateDeclaration { .. } => "E0202",
+            TopLevelReturn { .. } => "E0203",
+            InitializerReturnValue { .. } => "E0204",
+            ThisOutsideClass { .. } => "E0205",
+            SuperOutsideClass { .. } => "E0206",
+            SuperWithoutSuperclass { .. } => "E0207",
+            InheritFromSelf { .. } => "E0208",
+        };
+        let diagnostic = Diagnostic::error(code, self.to_string()).with_span(self.snippet().clone());
+        match self {
+            DuplicateDeclaration { .. } => diagnostic.with_note("only global variables can be declared again"),
+            InitializerReturnValue { .. } => diagnostic
+                .with_note("an initializer always returns `this`")
+                .with_help("use `return;` to return early"),
+            SuperWithoutSuperclass { .. } => diagnostic.with_help("declare a superclass with `class Name < Superclass`"),
+            _ => diagnostic,
+        }
+    }
+}
+
+impl ToDiagnostic for compiler::Error {
+    fn to_diagnostic(&self) -> Diagnostic {
+        use compiler::Error::*;
+        let code = match self {
+            TooManyLocals { .. } => "E0301",
+            TooManyUpvalues { .. } => "E0302",
+            TooManyConstants { .. } => "E0303",
+            JumpTooLarge { .. } => "E0304",
+        };
+        let diagnostic = Diagnostic::error(code, self.to_string()).with_span(self.snippet().clone());
+        match self {
+            TooManyLocals { .. } | TooManyConstants { .. } | JumpTooLarge { .. } => {
+                diagnostic.with_help("split the function into smaller ones")
+            },
+            _ => diagnostic,
+        }
+    }
+}
+
+impl ToDiagnostic for RuntimeError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        use RuntimeError::*;
+        let code = match self {
+            NumberOperand { .. } => "E0401",
+            NumberOperands { .. } => "E0402",
+            AddOperands { .. } => "E0403",
+            UndefinedVariable { .. } => "E0404",
+            NotCallable { .. } => "E0405",
+            ArityMismatch { .. } => "E0406",
+            StackOverflow { .. } => "E0407",
+            NotInstance { .. } => "E0408",
+            UndefinedProperty { .. } => "E0409",
+            InvalidSuperclass { .. } => "E0410",
+            InvalidArgument { .. } => "E0411",
+            InvalidProperty { .. } => "E0412",
+            Native { .. } => "E0413",
+            CorruptBytecode { .. } => "E0414",
+        };
+        let diagnostic = Diagnostic::error(code, self.to_string()).with_span(self.snippet().clone());
+        match self {
+            StackOverflow { .. } => diagnostic.with_note("a recursive function may be missing its base case"),
+            _ => diagnostic,
+        }
+    }
+}
+
+impl ToDiagnostic for loxc::Error {
+    fn to_diagnostic(&self) -> Diagnostic {
+        use loxc::Error::*;
+        let code = match self {
+            NotLoxc => "E0501",
+            VersionMismatch { .. } => "E0502",
+            Corrupt { .. } => "E0503",
+        };
+        let diagnostic = Diagnostic::error(code, self.to_string());
+        match self {
+            VersionMismatch { .. } | Corrupt { .. } => diagnostic.with_help("recompile it from its source"),
+            _ => diagnostic,
+        }
+    }
+}
+
+impl ToDiagnostic for io::Error {
+    fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::error("E0601", self.to_string())
+    }
+}
+
+/// Renders diagnostics as colored text for terminals, plain text for logs, or JSON for tools.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Renderer {
+    Colored,
+    Plain,
+    /// One line of plain text for each diagnostic, prefixed with its file and position.
+    Short,
+    /// One JSON object per line for each diagnostic.
+    Json,
+}
+
+impl Renderer {
+    pub fn render(&self, diagnostics: &[Diagnostic]) -> String {
+        let mut out = String::new();
+        for diagnostic in diagnostics {
+            match self {
+                Renderer::Colored | Renderer::Plain => self.render_human(&mut out, diagnostic),
+                Renderer::Short => Self::render_short(&mut out, diagnostic),
+                Renderer::Json => writeln!(out, "{}", Self::json(diagnostic)).unwrap(),
+            }
+        }
+        out
+    }
+
+    /// Renders a diagnostic the way `rustc` does, with a `-->` header naming the file and the position of its span
+    /// above the lines of code its spans are found at, which are numbered in a gutter.
+    fn render_human(&self, out: &mut String, diagnostic: &Diagnostic) {
+        let header = format!("{}[{}]", diagnostic.severity.name(), diagnostic.code);
+        writeln!(out, "{}: {}", self.severity(diagnostic.severity, &header), diagnostic.message).unwrap();
+
+        let spans = diagnostic.span.iter().chain(diagnostic.labels.iter().map(|l| &l.span));
+        let width = spans
+            .filter_map(|span| Some(span.position()?.line + span.lines()?.len()))
+            .map(|line| line.to_string().len())
+            .max();
+        let Some(width) = width else {
+            for note in &diagnostic.notes {
+                writeln!(out, "= {}: {note}", self.bold("note")).unwrap();
+            }
+            if let Some(help) = &diagnostic.help {
+                writeln!(out, "= {}: {help}", self.bold("help")).unwrap();
+            }
+            out.push('\n');
+            return;
+        };
+
+        let pad = " ".repeat(width);
+        let gutter = self.gutter("|");
+        let mut file = None;
+        if let Some(span) = &diagnostic.span && span.position().is_some() {
+            writeln!(out, "{pad}{} {}", self.gutter("-->"), Self::location(span)).unwrap();
+            writeln!(out, "{pad} {gutter}").unwrap();
+            self.render_snippet(out, width, span, diagnostic.severity, "^", "");
+            file = span.file();
+        }
+        for label in diagnostic.labels.iter().filter(|l| l.span.position().is_some()) {
+            if file.is_none() {
+                writeln!(out, "{pad}{} {}", self.gutter("-->"), Self::location(&label.span)).unwrap();
+            } else if file != label.span.file() {
+                writeln!(out, "{pad}{} {}", self.gutter(":::"), Self::location(&label.span)).unwrap();
+            }
+            writeln!(out, "{pad} {gutter}").unwrap();
+            self.render_snippet(out, width, &label.span, Severity::Note, "-", &label.message);
+            file = label.span.file();
+        }
+
+        if !diagnostic.notes.is_empty() || diagnostic.help.is_some() {
+            writeln!(out, "{pad} {gutter}").unwrap();
+        }
+        for note in &diagnostic.notes {
+            writeln!(out, "{pad} {} {}: {note}", self.gutter("="), self.bold("note")).unwrap();
+        }
+        if let Some(help) = &diagnostic.help {
+            writeln!(out, "{pad} {} {}: {help}", self.gutter("="), self.bold("help")).unwrap();
+        }
+        out.push('\n');
+    }
+
+    fn render_short(out: &mut String, diagnostic: &Diagnostic) {
+        let pos = diagnostic.span.as_ref().and_then(Snippet::position);
+        match (diagnostic.file_name(), pos) {
+            (Some(file), Some(pos)) => write!(out, "{file}:{}:{}: ", pos.line + 1, pos.column + 1),
+            (Some(file), None) => write!(out, "{file}: "),
+            (None, Some(pos)) => write!(out, "{}:{}: ", pos.line + 1, pos.column + 1),
+            (None, None) => Ok(()),
+        }.unwrap();
+        writeln!(out, "{}[{}]: {}", diagnostic.severity.name(), diagnostic.code, diagnostic.message).unwrap();
+    }
+
+    /// Renders the lines of a resolved snippet with `marker` repeated under its range on each of them, followed by
+    /// `message` on the last one. The lines in the middle of a range over more than `MAX_SNIPPET_LINES` are elided.
+    fn render_snippet(
+        &self,
+        out: &mut String,
+        width: usize,
+        span: &Snippet,
+        severity: Severity,
+        marker: &str,
+        message: &str,
+    ) {
+        let (Some(pos), Some(end), Some(lines)) = (span.position(), span.end_position(), span.lines()) else {
+            return;
+        };
+        let last = lines.len() - 1;
+        let pad = " ".repeat(width);
+        for (i, code) in lines.iter().enumerate() {
+            if lines.len() > MAX_SNIPPET_LINES && (2..last).contains(&i) {
+                if i == 2 {
+                    writeln!(out, "{}", self.gutter("...")).unwrap();
+                }
+                continue;
+            }
+
+            let line = format!("{:<width$}", pos.line + i + 1);
+            match code.as_str() {
+                "" => writeln!(out, "{} {}", self.gutter(&line), self.gutter("|")),
+                code => writeln!(out, "{} {} {}", self.gutter(&line), self.gutter("|"), expand_tabs(code)),
+            }.unwrap();
+
+            // The columns are counted in chars, but the markers are aligned by the widths of the chars on terminals.
+            let chars = code.chars().collect::<Vec<_>>();
+            let start = if i == 0 { pos.column.min(chars.len()) } else { 0 };
+            let stop = if pos.line + i == end.line { end.column.min(chars.len()) } else { chars.len() };
+            if i > 0 && stop <= start {
+                continue;
+            }
+            let spaces = " ".repeat(display_width(&chars[..start]));
+            let markers = marker.repeat(display_width(&chars[start..stop.max(start)]).max(1));
+            let markers = if i == last && !message.is_empty() { format!("{markers} {message}") } else { markers };
+            writeln!(out, "{pad} {} {spaces}{}", self.gutter("|"), self.severity(severity, &markers)).unwrap();
+        }
+    }
+
+    /// Returns the file name and the 1-based position of a resolved snippet, such as `script.lox:3:7`.
+    fn location(span: &Snippet) -> String {
+        let (name, pos) = (span.file_name().unwrap_or_default(), span.position().unwrap());
+        format!("{name}:{}:{}", pos.line + 1, pos.column + 1)
+    }
+
+    fn severity(&self, severity: Severity, text: &str) -> String {
+        match (self, severity) {
+            (Renderer::Colored, Severity::Error) => text.red().bold().to_string(),
+            (Renderer::Colored, Severity::Warning) => text.yellow().bold().to_string(),
+            (Renderer::Colored, _) => text.blue().bold().to_string(),
+            _ => text.to_string(),
+        }
+    }
+
+    fn gutter(&self, text: &str) -> String {
+        match self {
+            Renderer::Colored => text.blue().bold().to_string(),
+            _ => text.to_string(),
+        }
+    }
+
+    fn bold(&self, text: &str) -> String {
+        match self {
+            Renderer::Colored => text.bold().to_string(),
+            _ => text.to_string(),
+        }
+    }
+
+    /// Returns a diagnostic as JSON, whose spans have offsets in their files, 1-based lines and columns in chars,
+    /// which are null if they aren't resolved, and ends right after the last chars they cover.
+    fn json(diagnostic: &Diagnostic) -> serde_json::Value {
+        let span = |span: &Snippet| {
+            let (pos, end) = (span.position(), span.end_position());
+            let offset = span.file_offset().unwrap_or(span.offset());
+            json!({
+                "offset": offset,
+                "line": pos.map(|pos| pos.line + 1),
+                "column": pos.map(|pos| pos.column + 1),
+                "end": {
+                    "offset": offset + span.range().len(),
+                    "line": end.map(|end| end.line + 1),
+                    "column": end.map(|end| end.column + 1),
+                },
+                "code": span.code(),
+            })
+        };
+        json!({
+            "file": diagnostic.file_name(),
+            "severity": diagnostic.severity.name(),
+            "code": diagnostic.code,
+            "message": diagnostic.message,
+            "span": diagnostic.span.as_ref().map(span),
+            "labels": diagnostic.labels.iter()
+                .map(|label| json!({ "span": span(&label.span), "message": label.message }))
+                .collect::<Vec<_>>(),
+            "notes": diagnostic.notes,
+            "help": diagnostic.help,
+        })
+    }
+}
+
+/// Returns `code` with its tabs expanded to `TAB_WIDTH` spaces.
+fn expand_tabs(code: &str) -> String {
+    code.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+/// Returns the number of columns `chars` take up on terminals, where wide chars such as CJK ones take up 2 columns,
+/// combining marks take up none, and tabs take up `TAB_WIDTH` columns.
+fn display_width(chars: &[char]) -> usize {
+    chars.iter().map(|&c| if c == '\t' { TAB_WIDTH } else { c.width().unwrap_or(0) }).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::src::SnippetResolver;
+
+    fn diagnostic() -> Diagnostic {
+        let mut sources = SourceMap::new();
+        sources.add("a.lox", b"fun f() {}\n".as_slice());
+        sources.add("b.lox", b"var a = 1;\nprint a @ 2;".as_slice());
+        let diagnostic = Diagnostic::error("E0001", "unexpected char `@`")
+            .with_span(Snippet::new(31..32))
+            .with_label(Snippet::new(16..17), "declared here")
+            .with_label(Snippet::new(4..5), "defined here")
+            .with_note("a note")
+            .with_help("remove it");
+        SnippetResolver::new(&sources).resolve(diagnostic)
+    }
+
+    #[test]
+    fn render_plain() {
+        assert_eq!(Renderer::Plain.render(&[diagnostic()]), [
+            "error[E0001]: unexpected char `@`\n",
+            " --> b.lox:2:9\n",
+            "  |\n",
+            "2 | print a @ 2;\n",
+            "  |         ^\n",
+            "  |\n",
+            "1 | var a = 1;\n",
+            "  |     - declared here\n",
+            " ::: a.lox:1:5\n",
+            "  |\n",
+            "1 | fun f() {}\n",
+            "  |     - defined here\n",
+            "  |\n",
+            "  = note: a note\n",
+            "  = help: remove it\n",
+            "\n",
+        ].concat());
+
+        let diagnostic = Diagnostic::error("E0601", "file not found").with_help("check the path");
+        assert_eq!(Renderer::Plain.render(&[diagnostic]), "error[E0601]: file not found\n= help: check the path\n\n");
+    }
+
+    #[test]
+    fn render_plain_multiline() {
+        let mut sources = SourceMap::new();
+        sources.add("a.lox", b"var s = 'ab\ncd\n\nef\ngh\nij';\nprint s;".as_slice());
+        let resolver = SnippetResolver::new(&sources);
+
+        let diagnostic = Diagnostic::error("E0003", "multi-line string").with_span(Snippet::new(8..18));
+        assert_eq!(Renderer::Plain.render(&[resolver.resolve(diagnostic)]), [
+            "error[E0003]: multi-line string\n",
+            " --> a.lox:1:9\n",
+            "  |\n",
+            "1 | var s = 'ab\n",
+            "  |         ^^^\n",
+            "2 | cd\n",
+            "  | ^^\n",
+            "3 |\n",
+            "4 | ef\n",
+            "  | ^^\n",
+            "\n",
+        ].concat());
+
+        let diagnostic = Diagnostic::error("E0003", "multi-line string")
+            .with_label(Snippet::new(8..25), "started here");
+        assert_eq!(Renderer::Plain.render(&[resolver.resolve(diagnostic)]), [
+            "error[E0003]: multi-line string\n",
+            " --> a.lox:1:9\n",
+            "  |\n",
+            "1 | var s = 'ab\n",
+            "  |         ---\n",
+            "2 | cd\n",
+            "  | --\n",
+            "...\n",
+            "6 | ij';\n",
+            "  | --- started here\n",
+            "\n",
+        ].concat());
+    }
+
+    #[test]
+    fn render_plain_unicode() {
+        let mut sources = SourceMap::new();
+        sources.add("a.lox", "\tprint '가나' + e\u{301}x;".as_bytes());
+        let diagnostic = Diagnostic::error("E0401", "operands of `+` must be 2 numbers or 2 strings")
+            .with_span(Snippet::new(7..15))
+            .with_label(Snippet::new(21..22), "found here");
+        assert_eq!(Renderer::Plain.render(&[SnippetResolver::new(&sources).resolve(diagnostic)]), [
+            "error[E0401]: operands of `+` must be 2 numbers or 2 strings\n",
+            " --> a.lox:1:8\n",
+            "  |\n",
+            "1 |     print '가나' + e\u{301}x;\n",
+            "  |           ^^^^^^\n",
+            "  |\n",
+            "1 |     print '가나' + e\u{301}x;\n",
+            "  |                     - found here\n",
+            "\n",
+        ].concat());
+    }
+
+    #[test]
+    fn render_short() {
+        let diagnostics = [
+            diagnostic().with_file("c.lox"),
+            Diagnostic::error("E0601", "file not found").with_file("c.lox"),
+            Diagnostic::error("E0401", "undefined variable `a`").with_span(Snippet::new(3..4)),
+        ];
+        assert_eq!(Renderer::Short.render(&diagnostics), [
+            "b.lox:2:9: error[E0001]: unexpected char `@`\n",
+            "c.lox: error[E0601]: file not found\n",
+            "error[E0401]: undefined variable `a`\n",
+        ].concat());
+    }
+
+    #[test]
+    fn render_json() {
+        let diagnostics = [diagnostic(), Diagnostic::error("E0601", "file not found")];
+        let rendered = Renderer::Json.render(&diagnostics);
+        let lines = rendered.lines().map(|l| serde_json::from_str(l).unwrap()).collect::<Vec<serde_json::Value>>();
+        assert_eq!(lines, [
+            json!({
+                "file": "b.lox",
+                "severity": "error",
+                "code": "E0001",
+                "message": "unexpected char `@`",
+                "span": {
+                    "offset": 19,
+                    "line": 2,
+                    "column": 9,
+                    "end": { "offset": 20, "line": 2, "column": 10 },
+                    "code": "print a @ 2;",
+                },
+                "labels": [
+                    {
+                        "span": {
+                            "offset": 4,
+                            "line": 1,
+                            "column": 5,
+                            "end": { "offset": 5, "line": 1, "column": 6 },
+                            "code": "var a = 1;",
+                        },
+                        "message": "declared here",
+                    },
+                    {
+                        "span": {
+                            "offset": 4,
+                            "line": 1,
+                            "column": 5,
+                            "end": { "offset": 5, "line": 1, "column": 6 },
+                            "code": "fun f() {}",
+                        },
+                        "message": "defined here",
+                    },
+                ],
+                "notes": ["a note"],
+                "help": "remove it",
+            }),
+            json!({
+                "file": null,
+                "severity": "error",
+                "code": "E0601",
+                "message": "file not found",
+                "span": null,
+                "labels": [],
+                "notes": [],
+                "help": null,
+            }),
+        ]);
+    }
+
+    #[test]
+    fn error_to_diagnostic() {
+        let error = scanner::Error::UnterminatedDoubleQuoteString { snippet: Snippet::new(3..4) };
+        let diagnostic = error.to_diagnostic();
+        assert_eq!(diagnostic.code, "E0005");
+        assert_eq!(diagnostic.message, "unterminated double quote string");
+        assert_eq!(diagnostic.span.map(|s| s.offset()), Some(3));
+        assert_eq!(diagnostic.help.as_deref(), Some("close the string with `\"`"));
+    }
+}
@@ -0,0 +1,438 @@
+use std::collections::HashMap;
+use std::mem;
+use thiserror::Error;
+#[cfg(not(qlox_macros))]
+use qlox_macros::ResolveSnippet;
+use crate::ast::{
+    Accept, Assign, Binary, Block, Call, Class, Depth, Expr, ExprVisitor, Expression, Function, Get,
+    Grouping, If, Literal, Logical, Print, Return, Set, Stmt, StmtVisitor, Super, This, Unary, Var,
+    Variable, While,
+};
+use crate::src::Snippet;
+use crate::token::Token;
+
+#[derive(Error, Debug, PartialEq)]
+#[cfg_attr(not(qlox_macros), derive(ResolveSnippet))]
+#[non_exhaustive]
+pub enum Error {
+    #[error("can't read local variable `{name}` in its own initializer")]
+    ReadInOwnInitializer {
+        snippet: Snippet,
+        name: String,
+    },
+
+    #[error("already a variable named `{name}` in this scope")]
+    DuplicateDeclaration {
+        snippet: Snippet,
+        name: String,
+    },
+
+    #[error("can't return from top-level code")]
+    TopLevelReturn {
+        snippet: Snippet,
+    },
+
+    #[error("can't return a value from an initializer")]
+    InitializerReturnValue {
+        snippet: Snippet,
+    },
+
+    #[error("can't use `this` outside of a class")]
+    ThisOutsideClass {
+        snippet: Snippet,
+    },
+
+    #[error("can't use `super` outside of a class")]
+    SuperOutsideClass {
+        snippet: Snippet,
+    },
+
+    #[error("can't use `super` in a class with no superclass")]
+    SuperWithoutSuperclass {
+        snippet: Snippet,
+    },
+
+    #[error("a class can't inherit from itself")]
+    InheritFromSelf {
+        snippet: Snippet,
+    },
+}
+
+impl Error {
+    pub fn snippet(&self) -> &Snippet {
+        match self {
+            Error::ReadInOwnInitializer { snippet, .. } |
+            Error::DuplicateDeclaration { snippet, .. } |
+            Error::TopLevelReturn { snippet, .. } |
+            Error::InitializerReturnValue { snippet, .. } |
+            Error::ThisOutsideClass { snippet, .. } |
+            Error::SuperOutsideClass { snippet, .. } |
+            Error::SuperWithoutSuperclass { snippet, .. } |
+            Error::InheritFromSelf { snippet, .. } => snippet,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionKind {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClassKind {
+    None,
+    Class,
+    Subclass,
+}
+
+/// Resolves every local variable reference to the scope it's declared in, before running a program,
+/// and reports the semantic errors which can be found statically.
+pub struct Resolver {
+    /// The local scopes being resolved, where each variable is mapped to whether its initializer
+    /// has been resolved. Global variables are not tracked, and are looked up dynamically.
+    scopes: Vec<HashMap<String, bool>>,
+    function: FunctionKind,
+    class: ClassKind,
+    errors: Vec<Error>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            scopes: Vec::new(),
+            function: FunctionKind::None,
+            class: ClassKind::None,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn resolve(&mut self, stmts: &[Stmt]) -> Result<(), Vec<Error>> {
+        self.resolve_stmts(stmts);
+        if !self.errors.is_empty() {
+            return Err(mem::take(&mut self.errors));
+        }
+        Ok(())
+    }
+
+    fn resolve_stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            stmt.accept(&mut *self, ());
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        expr.accept(self, ());
+    }
+
+    fn resolve_function(&mut self, function: &Function, kind: FunctionKind) {
+        let enclosing = mem::replace(&mut self.function, kind);
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_stmts(&function.body);
+        self.end_scope();
+        self.function = enclosing;
+    }
+
+    fn resolve_local(&mut self, name: &str, depth: &Depth) {
+        if let Some(i) = self.scopes.iter().rev().position(|s| s.contains_key(name)) {
+            depth.set(Some(i));
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else { return; };
+        if scope.insert(name.lexeme.clone(), false).is_some() {
+            self.errors.push(Error::DuplicateDeclaration {
+                snippet: Snippet::new(name.range()),
+                name: name.lexeme.clone(),
+            });
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    /// Binds a keyword such as `this` or `super` in a new scope.
+    fn begin_keyword_scope(&mut self, keyword: &str) {
+        self.begin_scope();
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(keyword.to_string(), true);
+        }
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExprVisitor<(), ()> for &mut Resolver {
+    fn visit_assign(self, expr: &Assign, _: ()) {
+        self.resolve_expr(&expr.value);
+        self.resolve_local(&expr.name.lexeme, &expr.depth);
+    }
+
+    fn visit_binary(self, expr: &Binary, _: ()) {
+        self.resolve_expr(&expr.left);
+        self.resolve_expr(&expr.right);
+    }
+
+    fn visit_call(self, expr: &Call, _: ()) {
+        self.resolve_expr(&expr.callee);
+        for argument in &expr.arguments {
+            self.resolve_expr(argument);
+        }
+    }
+
+    fn visit_get(self, expr: &Get, _: ()) {
+        self.resolve_expr(&expr.object);
+    }
+
+    fn visit_grouping(self, expr: &Grouping, _: ()) {
+        self.resolve_expr(&expr.expr);
+    }
+
+    fn visit_literal(self, _: &Literal, _: ()) {}
+
+    fn visit_logical(self, expr: &Logical, _: ()) {
+        self.resolve_expr(&expr.left);
+        self.resolve_expr(&expr.right);
+    }
+
+    fn visit_set(self, expr: &Set, _: ()) {
+        self.resolve_expr(&expr.value);
+        self.resolve_expr(&expr.object);
+    }
+
+    fn visit_super(self, expr: &Super, _: ()) {
+        let snippet = Snippet::new(expr.keyword.range());
+        match self.class {
+            ClassKind::None => self.errors.push(Error::SuperOutsideClass { snippet }),
+            ClassKind::Class => self.errors.push(Error::SuperWithoutSuperclass { snippet }),
+            ClassKind::Subclass => self.resolve_local(&expr.keyword.lexeme, &expr.depth),
+        }
+    }
+
+    fn visit_this(self, expr: &This, _: ()) {
+        if self.class == ClassKind::None {
+            self.errors.push(Error::ThisOutsideClass {
+                snippet: Snippet::new(expr.keyword.range()),
+            });
+            return;
+        }
+        self.resolve_local(&expr.keyword.lexeme, &expr.depth);
+    }
+
+    fn visit_unary(self, expr: &Unary, _: ()) {
+        self.resolve_expr(&expr.right);
+    }
+
+    fn visit_variable(self, expr: &Variable, _: ()) {
+        let name = &expr.name;
+        if let Some(false) = self.scopes.last().and_then(|s| s.get(&name.lexeme)) {
+            self.errors.push(Error::ReadInOwnInitializer {
+                snippet: Snippet::new(name.range()),
+                name: name.lexeme.clone(),
+            });
+        }
+        self.resolve_local(&name.lexeme, &expr.depth);
+    }
+}
+
+impl StmtVisitor<(), ()> for &mut Resolver {
+    fn visit_block(self, stmt: &Block, _: ()) {
+        self.begin_scope();
+        self.resolve_stmts(&stmt.stmts);
+        self.end_scope();
+    }
+
+    fn visit_class(self, stmt: &Class, _: ()) {
+        let enclosing = mem::replace(&mut self.class, ClassKind::Class);
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+
+        if let Some(superclass) = &stmt.superclass {
+            if superclass.name.lexeme == stmt.name.lexeme {
+                self.errors.push(Error::InheritFromSelf {
+                    snippet: Snippet::new(superclass.name.range()),
+                });
+            }
+            self.class = ClassKind::Subclass;
+            (&mut *self).visit_variable(superclass, ());
+            self.begin_keyword_scope("super");
+        }
+
+        self.begin_keyword_scope("this");
+        for method in &stmt.methods {
+            let kind = if method.name.lexeme == "init" {
+                FunctionKind::Initializer
+            } else {
+                FunctionKind::Method
+            };
+            self.resolve_function(method, kind);
+        }
+        self.end_scope();
+
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+        self.class = enclosing;
+    }
+
+    fn visit_expression(self, stmt: &Expression, _: ()) {
+        self.resolve_expr(&stmt.expr);
+    }
+
+    fn visit_function(self, stmt: &Function, _: ()) {
+        // A function is defined before its body is resolved, so that it can refer to itself recursively.
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+        self.resolve_function(stmt, FunctionKind::Function);
+    }
+
+    fn visit_if(self, stmt: &If, _: ()) {
+        self.resolve_expr(&stmt.condition);
+        stmt.then_branch.accept(&mut *self, ());
+        if let Some(else_branch) = &stmt.else_branch {
+            else_branch.accept(self, ());
+        }
+    }
+
+    fn visit_print(self, stmt: &Print, _: ()) {
+        self.resolve_expr(&stmt.expr);
+    }
+
+    fn visit_return(self, stmt: &Return, _: ()) {
+        if self.function == FunctionKind::None {
+            self.errors.push(Error::TopLevelReturn {
+                snippet: Snippet::new(stmt.keyword.range()),
+            });
+        }
+
+        if let Some(value) = &stmt.value {
+            if self.function == FunctionKind::Initializer {
+                self.errors.push(Error::InitializerReturnValue {
+                    snippet: Snippet::new(stmt.keyword.range()),
+                });
+            }
+            self.resolve_expr(value);
+        }
+    }
+
+    fn visit_var(self, stmt: &Var, _: ()) {
+        // A variable is declared before its initializer is resolved, so that the initializer
+        // can't refer to the variable being declared, even if it shadows an outer one.
+        self.declare(&stmt.name);
+        if let Some(initializer) = &stmt.initializer {
+            self.resolve_expr(initializer);
+        }
+        self.define(&stmt.name);
+    }
+
+    fn visit_while(self, stmt: &While, _: ()) {
+        self.resolve_expr(&stmt.condition);
+        stmt.body.accept(self, ());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn resolve(source: &str) -> Result<Vec<Stmt>, Vec<Error>> {
+        let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+        let stmts = Parser::new(&tokens).parse().unwrap();
+        Resolver::new().resolve(&stmts).map(|_| stmts)
+    }
+
+    #[test]
+    fn resolver_resolve() {
+        let stmts = resolve("var a; { var b; fun f() { a; b; { b = 1; } } }").unwrap();
+        let Stmt::Block(block) = &stmts[1] else { panic!() };
+        let Stmt::Function(function) = &block.stmts[1] else { panic!() };
+        let depth = |i: usize| match &function.body[i] {
+            Stmt::Expression(Expression { expr: Expr::Variable(v) }) => v.depth.get(),
+            Stmt::Block(Block { stmts }) => match &stmts[0] {
+                Stmt::Expression(Expression { expr: Expr::Assign(a) }) => a.depth.get(),
+                _ => panic!(),
+            },
+            _ => panic!(),
+        };
+
+        assert_eq!(depth(0), None);
+        assert_eq!(depth(1), Some(1));
+        assert_eq!(depth(2), Some(2));
+    }
+
+    #[test]
+    fn resolver_resolve_error() {
+        let source = r#"
+            { var a = a; }
+            { var b; var b; }
+            fun f(c, c) {}
+            return;
+            class A { init() { return 1; } }
+            this;
+            super.m();
+            class B { m() { super.m(); } }
+            class C < C {}
+        "#;
+        let span = |s: &str, lexeme: &str| {
+            let offset = source.find(s).unwrap();
+            offset..offset + lexeme.len()
+        };
+        let errors = resolve(source).err().unwrap();
+        assert_eq!(errors, vec![
+            Error::ReadInOwnInitializer {
+                snippet: Snippet::new(span("a; }", "a")),
+                name: "a".to_string(),
+            },
+            Error::DuplicateDeclaration {
+                snippet: Snippet::new(span("b; }", "b")),
+                name: "b".to_string(),
+            },
+            Error::DuplicateDeclaration {
+                snippet: Snippet::new(span("c) {}", "c")),
+                name: "c".to_string(),
+            },
+            Error::TopLevelReturn {
+                snippet: Snippet::new(span("return;", "return")),
+            },
+            Error::InitializerReturnValue {
+                snippet: Snippet::new(span("return 1", "return")),
+            },
+            Error::ThisOutsideClass {
+                snippet: Snippet::new(span("this;", "this")),
+            },
+            Error::SuperOutsideClass {
+                snippet: Snippet::new(span("super.m();", "super")),
+            },
+            Error::SuperWithoutSuperclass {
+                snippet: Snippet::new(span("super.m(); }", "super")),
+            },
+            Error::InheritFromSelf {
+                snippet: Snippet::new(span("C {}", "C")),
+            },
+        ]);
+    }
+}
@@ -1,94 +1,180 @@
+use std::iter::Peekable;
+use std::mem;
+use std::ops::Range;
+use std::str::CharIndices;
 use thiserror::Error;
+#[cfg(not(qlox_macros))]
 use qlox_macros::ResolveSnippet;
-use crate::consts::tag::ERROR;
 use crate::src::{Index, Snippet};
 use crate::token;
 use crate::token::{Token, TokenKind};
 use crate::types::Number;
-use crate::utils::string::{Substring, SubstringError};
+use crate::utils::string::Substring;
 
-#[derive(Error, Debug, ResolveSnippet, PartialEq)]
+#[derive(Error, Debug, PartialEq)]
+#[cfg_attr(not(qlox_macros), derive(ResolveSnippet))]
+#[non_exhaustive]
 pub enum Error {
-    #[error("{ERROR}: unexpected char `{c}`\n\n{snippet}\n")]
+    #[error("unexpected char `{c}`")]
     UnexpectedChar {
         snippet: Snippet,
         c: char,
     },
 
-    #[error("{ERROR}: invalid utf-8 char\n\n{snippet}\n")]
+    #[error("invalid utf-8 char")]
     InvalidUtf8Char {
         snippet: Snippet,
     },
 
-    #[error("{ERROR}: unterminated multi-line comment\n\n{snippet}\n")]
+    #[error("unterminated multi-line comment")]
     UnterminatedMultiLineComment {
         snippet: Snippet,
     },
 
-    #[error("{ERROR}: unterminated single quote string\n\n{snippet}\n")]
+    #[error("unterminated single quote string")]
     UnterminatedSingleQuoteString {
         snippet: Snippet,
     },
 
-    #[error("{ERROR}: unterminated double quote string\n\n{snippet}\n")]
+    #[error("unterminated double quote string")]
     UnterminatedDoubleQuoteString {
         snippet: Snippet,
     },
+
+    #[error("invalid escape sequence `{escape}`")]
+    InvalidEscape {
+        snippet: Snippet,
+        escape: String,
+    },
+
+    #[error("unterminated string interpolation")]
+    UnterminatedInterpolation {
+        snippet: Snippet,
+    },
+
+    #[error("unterminated raw string")]
+    UnterminatedRawString {
+        snippet: Snippet,
+        hashes: usize,
+    },
+
+    #[error("unterminated multi-line string")]
+    UnterminatedMultiLineString {
+        snippet: Snippet,
+        quote: char,
+    },
+
+    #[error("interpolation in multi-line string")]
+    InterpolationInMultiLineString {
+        snippet: Snippet,
+    },
 }
 
-impl From<SubstringError> for Error {
-    fn from(error: SubstringError) -> Self {
-        Error::InvalidUtf8Char {
-            snippet: Snippet::new(error.range.start + error.source.utf8_error().valid_up_to()),
+impl Error {
+    pub fn snippet(&self) -> &Snippet {
+        match self {
+            Error::UnexpectedChar { snippet, .. } |
+            Error::InvalidUtf8Char { snippet, .. } |
+            Error::UnterminatedMultiLineComment { snippet, .. } |
+            Error::UnterminatedSingleQuoteString { snippet, .. } |
+            Error::UnterminatedDoubleQuoteString { snippet, .. } |
+            Error::InvalidEscape { snippet, .. } |
+            Error::UnterminatedInterpolation { snippet, .. } |
+            Error::UnterminatedRawString { snippet, .. } |
+            Error::UnterminatedMultiLineString { snippet, .. } |
+            Error::InterpolationInMultiLineString { snippet, .. } => snippet,
         }
     }
 }
 
 pub struct Scanner<'a> {
     source: &'a [u8],
+    /// The offset `source` starts at in the source map it's added to.
+    base: Index,
     next: Index,
     token_start: Index,
+    /// The strings whose interpolations are being scanned, the innermost of which is the last.
+    interpolations: Vec<Interpolation>,
+    /// The string whose interpolation has just ended, which is scanned on from the next char.
+    resumed: Option<Interpolation>,
+    /// The errors found so far, which are all returned once the whole source is scanned.
+    errors: Vec<Error>,
+}
+
+/// A string holding an interpolation `${ expr }`, which is scanned as the tokens of `expr`
+/// between the parts of the string before and after it.
+struct Interpolation {
+    quote: u8,
+    /// The offset of the opening quote of the string.
+    string_start: Index,
+    /// The offset of the `${` of the interpolation.
+    start: Index,
+    /// The number of braces opened in the interpolation and not closed yet.
+    braces: usize,
 }
 
 impl<'a> Scanner<'a> {
     pub fn new(source: &'a [u8]) -> Self {
+        Self::with_offset(source, 0)
+    }
+
+    /// Creates a scanner of `source` starting at `base` in a `SourceMap`,
+    /// which offsets the tokens and errors it finds by `base`.
+    pub fn with_offset(source: &'a [u8], base: Index) -> Self {
         Scanner {
             source,
+            base,
             next: 0,
             token_start: 0,
+            interpolations: Vec::new(),
+            resumed: None,
+            errors: Vec::new(),
         }
     }
 
     pub fn scan_tokens(&mut self) -> Result<Vec<Token>, Vec<Error>> {
         let mut tokens = Vec::new();
-        let mut errors = Vec::new();
-        while let Some(&c) = self.next() {
-            self.token_start = self.next - 1;
-
-            let token = self.scan_token(c)
+        loop {
+            self.token_start = self.next;
+            let token = match self.resumed.take() {
+                Some(string) => self.scan_string(string.quote, string.string_start).map(Some),
+                None => {
+                    let Some(&c) = self.next() else { break };
+                    self.scan_token(c)
+                },
+            };
+
+            let token = token
                 .and_then(|ok| ok.map(|k| {
                     Ok::<Token, Error>(Token {
                         kind: k,
-                        lexeme: self.source.substring(self.token_start..self.next)?,
-                        offset: self.token_start,
+                        lexeme: self.substring(self.token_start..self.next)?,
+                        offset: self.base + self.token_start,
                     })
                 }).transpose());
 
             match token {
                 Ok(Some(token)) => tokens.push(token),
                 Ok(None) => (),
-                Err(e) => errors.push(e),
+                Err(e) => self.errors.push(e),
             }
         }
 
-        if errors.len() > 0 {
-            return Err(errors);
+        // Only the innermost interpolation is reported, which is left open inside the others.
+        if let Some(interpolation) = self.interpolations.pop() {
+            self.errors.push(Error::UnterminatedInterpolation {
+                snippet: Snippet::new(self.base + interpolation.start..self.base + self.source.len()),
+            });
+        }
+
+        if !self.errors.is_empty() {
+            return Err(mem::take(&mut self.errors));
         }
 
         tokens.push(Token {
             kind: TokenKind::Eof,
             lexeme: String::new(),
-            offset: self.next - 1,
+            offset: self.base + self.next - 1,
         });
         Ok(tokens)
     }
@@ -98,8 +184,25 @@ impl<'a> Scanner<'a> {
             b' ' | b'\t' | b'\r' | b'\n' => Ok(None),
             b'(' => Ok(Some(TokenKind::LeftParen)),
             b')' => Ok(Some(TokenKind::RightParen)),
-            b'{' => Ok(Some(TokenKind::LeftBrace)),
-            b'}' => Ok(Some(TokenKind::RightBrace)),
+            b'{' => {
+                if let Some(interpolation) = self.interpolations.last_mut() {
+                    interpolation.braces += 1;
+                }
+                Ok(Some(TokenKind::LeftBrace))
+            },
+            b'}' => {
+                match self.interpolations.last_mut() {
+                    Some(interpolation) if interpolation.braces == 0 => {
+                        self.resumed = self.interpolations.pop();
+                        Ok(Some(TokenKind::InterpolationEnd))
+                    },
+                    Some(interpolation) => {
+                        interpolation.braces -= 1;
+                        Ok(Some(TokenKind::RightBrace))
+                    },
+                    None => Ok(Some(TokenKind::RightBrace)),
+                }
+            },
             b',' => Ok(Some(TokenKind::Comma)),
             b'.' => Ok(Some(TokenKind::Dot)),
             b'-' => Ok(Some(TokenKind::Minus)),
@@ -144,8 +247,18 @@ impl<'a> Scanner<'a> {
                     TokenKind::Less
                 }))
             },
+            b'\'' | b'"' if self.peek() == Some(&c) && self.peek_next() == Some(&c) => {
+                self.scan_multi_line_string(c).map(Some)
+            },
             b'\'' | b'"' => {
-                self.scan_string(c).map(Some)
+                self.scan_string(c, self.token_start).map(Some)
+            },
+            b'r' if self.is_raw_string() => {
+                self.scan_raw_string().map(Some)
+            },
+            // A `${` is scanned only where `scan_string` has found an interpolation.
+            b'$' if self.interpolations.last().is_some_and(|i| i.start == self.token_start) && self.matches(b'{') => {
+                Ok(Some(TokenKind::InterpolationStart))
             },
             _ if c.is_ascii_digit() => {
                 self.scan_number().map(Some)
@@ -155,7 +268,7 @@ impl<'a> Scanner<'a> {
             },
             _ => {
                 Err(Error::UnexpectedChar {
-                    snippet: Snippet::new(self.token_start),
+                    snippet: Snippet::new(self.base + self.token_start..self.base + self.next),
                     c: c as char,
                 })
             },
@@ -175,9 +288,9 @@ impl<'a> Scanner<'a> {
             self.go_next();
         }
 
-        if let None = self.peek_next() {
+        if self.peek_next().is_none() {
             Err(Error::UnterminatedMultiLineComment {
-                snippet: Snippet::new(self.token_start),
+                snippet: Snippet::new(self.base + self.token_start..self.base + self.source.len()),
             })
         } else {
             self.go_step(2);
@@ -185,28 +298,197 @@ impl<'a> Scanner<'a> {
         }
     }
 
-    fn scan_string(&mut self, quote: u8) -> Result<TokenKind, Error> {
+    /// Scans the string quoted by `quote` at `string_start` from the next char, up to its closing quote
+    /// or up to its next interpolation, the latter of which is scanned as a string part.
+    /// A string holding interpolations is scanned as string parts, including the part after the last interpolation.
+    fn scan_string(&mut self, quote: u8, string_start: Index) -> Result<TokenKind, Error> {
+        let content_start = self.next;
         while let Some(&c) = self.peek() {
             if c == quote { break; }
+            if c == b'$' && self.peek_next() == Some(&b'{') {
+                self.interpolations.push(Interpolation {
+                    quote,
+                    string_start,
+                    start: self.next,
+                    braces: 0,
+                });
+                return self.unescape(content_start..self.next).map(TokenKind::StringPart);
+            }
+            // The char after a backslash is escaped, so an escaped quote doesn't end the string.
+            if c == b'\\' { self.go_next(); }
             self.go_next();
         }
 
-        if let None = self.peek() {
+        if self.peek().is_none() {
+            let snippet = Snippet::new(self.base + string_start..self.base + self.source.len());
             if quote == b'\'' {
-                Err(Error::UnterminatedSingleQuoteString {
-                    snippet: Snippet::new(self.token_start),
-                })
+                Err(Error::UnterminatedSingleQuoteString { snippet })
             } else {
-                Err(Error::UnterminatedDoubleQuoteString {
-                    snippet: Snippet::new(self.token_start),
-                })
+                Err(Error::UnterminatedDoubleQuoteString { snippet })
             }
         } else {
             self.go_next();
-            Ok(TokenKind::String(
-                self.source.substring(self.token_start + 1..self.next - 1)?
-            ))
+            let s = self.unescape(content_start..self.next - 1)?;
+            if self.token_start == string_start {
+                Ok(TokenKind::String(s))
+            } else {
+                Ok(TokenKind::StringPart(s))
+            }
+        }
+    }
+
+    /// Scans a string quoted by three `quote`s, whose lines are stripped of their common leading whitespace,
+    /// and which leaves out the line break right after its opening quotes and the blank line before its closing ones.
+    /// It isn't interpolated, so each `${` in it is an error unless its `$` is escaped by `\$`.
+    fn scan_multi_line_string(&mut self, quote: u8) -> Result<TokenKind, Error> {
+        self.go_step(2);
+        let content_start = self.next;
+        let closing = [quote; 3];
+        loop {
+            match self.peek() {
+                None => {
+                    return Err(Error::UnterminatedMultiLineString {
+                        snippet: Snippet::new(self.base + self.token_start..self.base + self.source.len()),
+                        quote: quote as char,
+                    });
+                },
+                _ if self.source[self.next..].starts_with(&closing) => break,
+                // The char after a backslash is escaped, so an escaped quote doesn't end the string.
+                Some(b'\\') => self.go_step(2),
+                Some(b'$') if self.peek_next() == Some(&b'{') => {
+                    self.errors.push(Error::InterpolationInMultiLineString {
+                        snippet: Snippet::new(self.base + self.next..self.base + self.next + 2),
+                    });
+                    self.go_step(2);
+                },
+                _ => self.go_next(),
+            }
+        }
+        let content_end = self.next;
+        self.go_step(3);
+
+        let source = self.source;
+        let is_blank = |line: &Range<Index>| source[line.clone()].iter().all(|&c| c == b' ' || c == b'\t');
+        let mut lines = Vec::new();
+        let mut line_start = content_start;
+        for end in (content_start..content_end).filter(|&i| self.source[i] == b'\n').chain([content_end]) {
+            let line_end = if end > line_start && self.source[end - 1] == b'\r' { end - 1 } else { end };
+            lines.push(line_start..line_end);
+            line_start = end + 1;
+        }
+        if lines.len() > 1 && is_blank(&lines[0]) {
+            lines.remove(0);
+        }
+        if lines.len() > 1 && is_blank(&lines[lines.len() - 1]) {
+            lines.pop();
+        }
+
+        // The indentation is the longest leading whitespace which all non-blank lines share byte for byte,
+        // so a tab is never taken for some spaces.
+        let mut indent: Option<&[u8]> = None;
+        for line in lines.iter().filter(|line| !is_blank(line)) {
+            let line = &self.source[line.clone()];
+            let whitespace = &line[..line.iter().take_while(|&&c| c == b' ' || c == b'\t').count()];
+            let common = indent.map_or(whitespace.len(), |indent| {
+                indent.iter().zip(whitespace).take_while(|(a, b)| a == b).count()
+            });
+            indent = Some(&whitespace[..common]);
+        }
+        let indent = indent.map_or(0, <[u8]>::len);
+        let mut unescaped = Vec::with_capacity(lines.len());
+        for line in lines {
+            unescaped.push(if is_blank(&line) { String::new() } else { self.unescape(line.start + indent..line.end)? });
+        }
+        Ok(TokenKind::String(unescaped.join("\n")))
+    }
+
+    /// Returns whether the `r` just scanned starts a raw string, which is followed by hashes, if any, and a `"`.
+    fn is_raw_string(&self) -> bool {
+        let hashes = self.source[self.next..].iter().take_while(|&&c| c == b'#').count();
+        self.source.get(self.next + hashes) == Some(&b'"')
+    }
+
+    /// Scans a raw string `r"..."`, whose chars are taken as they are, without escape sequences and interpolations.
+    /// It may be guarded by hashes, such as `r#"..."#`, which ends only at a `"` followed by as many hashes.
+    fn scan_raw_string(&mut self) -> Result<TokenKind, Error> {
+        let mut hashes = 0;
+        while self.matches(b'#') {
+            hashes += 1;
+        }
+        self.go_next();
+
+        let content_start = self.next;
+        let closing = [b'"'].into_iter().chain([b'#'].repeat(hashes)).collect::<Vec<_>>();
+        let Some(len) = self.source[content_start..].windows(closing.len()).position(|w| w == closing) else {
+            self.next = self.source.len();
+            return Err(Error::UnterminatedRawString {
+                snippet: Snippet::new(self.base + self.token_start..self.base + self.source.len()),
+                hashes,
+            });
+        };
+        self.next = content_start + len + closing.len();
+        self.substring(content_start..content_start + len).map(TokenKind::String)
+    }
+
+    /// Returns the string in `range` of the source with its escape sequences replaced by the chars they stand for.
+    /// Each escape sequence which isn't valid is left out of the string, and is added to the errors
+    /// to be returned by `scan_tokens`, so that all of them in the string are reported.
+    fn unescape(&mut self, range: Range<Index>) -> Result<String, Error> {
+        let raw = self.substring(range.clone())?;
+        let mut s = String::with_capacity(raw.len());
+        let mut chars = raw.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if c != '\\' {
+                s.push(c);
+                continue;
+            }
+
+            match Self::escape(&mut chars) {
+                Some(c) => s.push(c),
+                None => {
+                    let end = chars.peek().map_or(raw.len(), |&(j, _)| j);
+                    let start = self.base + range.start;
+                    self.errors.push(Error::InvalidEscape {
+                        snippet: Snippet::new(start + i..start + end),
+                        escape: raw[i..end].to_string(),
+                    });
+                },
+            }
+        }
+        Ok(s)
+    }
+
+    /// Consumes an escape sequence after its backslash, and returns the char it stands for,
+    /// or `None` if it isn't valid, having consumed the chars up to where it's found invalid.
+    fn escape(chars: &mut Peekable<CharIndices>) -> Option<char> {
+        match chars.next()?.1 {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '\\' => Some('\\'),
+            '\'' => Some('\''),
+            '"' => Some('"'),
+            '$' => Some('$'),
+            '0' => Some('\0'),
+            // `\xNN` stands for an ASCII char, since the bytes above `\x7F` aren't chars in UTF-8.
+            'x' => Self::hex(chars, 2).filter(|&(n, len)| len == 2 && n <= 0x7F).and_then(|(n, _)| char::from_u32(n)),
+            'u' => {
+                chars.next_if(|&(_, c)| c == '{')?;
+                let (n, _) = Self::hex(chars, 6)?;
+                chars.next_if(|&(_, c)| c == '}')?;
+                char::from_u32(n)
+            },
+            _ => None,
+        }
+    }
+
+    /// Consumes up to `max` hex digits, and returns the number they stand for with the number of them.
+    fn hex(chars: &mut Peekable<CharIndices>, max: usize) -> Option<(u32, usize)> {
+        let mut digits = String::new();
+        while digits.len() < max && let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_hexdigit()) {
+            digits.push(c);
         }
+        u32::from_str_radix(&digits, 16).ok().map(|n| (n, digits.len()))
     }
 
     fn scan_number(&mut self) -> Result<TokenKind, Error> {
@@ -215,18 +497,17 @@ impl<'a> Scanner<'a> {
             self.go_next();
         }
 
-        if let (Some(&c), Some(d)) = (self.peek(), self.peek_next()) {
-            if c == b'.' && d.is_ascii_digit() {
-                self.go_step(2);
-                while let Some(c) = self.peek() {
-                    if !c.is_ascii_digit() { break; }
-                    self.go_next();
-                }
+        if let (Some(&c), Some(d)) = (self.peek(), self.peek_next())
+            && c == b'.' && d.is_ascii_digit() {
+            self.go_step(2);
+            while let Some(c) = self.peek() {
+                if !c.is_ascii_digit() { break; }
+                self.go_next();
             }
         }
 
         Ok(TokenKind::Number(
-            self.source.substring(self.token_start..self.next)?
+            self.substring(self.token_start..self.next)?
                 .parse::<Number>()
                 .unwrap()
         ))
@@ -238,13 +519,23 @@ impl<'a> Scanner<'a> {
             self.go_next();
         }
 
-        let s = self.source.substring(self.token_start..self.next)?;
+        let s = self.substring(self.token_start..self.next)?;
         match token::KEYWORDS.get(s.as_str()) {
             Some(kind) => Ok(kind.clone()),
             None => Ok(TokenKind::Identifier(s)),
         }
     }
 
+    /// Returns the code in `range` of the source, or an error at the first byte of it which isn't UTF-8.
+    fn substring(&self, range: Range<Index>) -> Result<String, Error> {
+        self.source.substring(range).map_err(|e| {
+            let offset = self.base + e.range.start + e.source.utf8_error().valid_up_to();
+            Error::InvalidUtf8Char {
+                snippet: Snippet::new(offset..offset + 1),
+            }
+        })
+    }
+
     #[inline(always)]
     fn next(&mut self) -> Option<&u8> {
         let c = self.source.get(self.next);
@@ -355,13 +646,145 @@ mod tests {
         }
     }
 
+    #[test]
+    fn scanner_scan_tokens_raw_string() {
+        let cases = [
+            (r#"r"a\nb""#, r"a\nb"),
+            (r#"r"${a}\""#, r"${a}\"),
+            (r##"r#"say "hi""#"##, r#"say "hi""#),
+            (r###"r##"a"#b"##"###, r##"a"#b"##),
+            ("r\"a\nb\"", "a\nb"),
+        ];
+
+        for (source, expected) in cases {
+            let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+            assert_eq!(tokens[0], new_token(TokenKind::String(expected.to_string()), source, 0), "{source}");
+        }
+
+        // `r` not followed by a `"` is an identifier.
+        let tokens = Scanner::new(b"r + r#").scan_tokens().err().unwrap();
+        assert_eq!(tokens, [Error::UnexpectedChar {
+            snippet: Snippet::new(5..6),
+            c: '#',
+        }]);
+    }
+
+    #[test]
+    fn scanner_scan_tokens_multi_line_string() {
+        let cases = [
+            ("'''a'''", "a"),
+            ("\"\"\"\"\"\"", ""),
+            ("\"\"\"\n    a\n      \"b\"\n\n    c\n    \"\"\"", "a\n  \"b\"\n\nc"),
+            ("'''\r\n\ta\\t'\r\n\t\tb\r\n\t'''", "a\t'\n\tb"),
+            ("'''  a\n  b  '''", "a\nb  "),
+            // Tabs and spaces aren't mixed up, so only the whitespace all lines begin with is stripped.
+            ("'''\n\ta\n    b\n'''", "\ta\n    b"),
+            ("'''\n  \ta\n    b\n  '''", "\ta\n  b"),
+            ("\"\"\"a\\\"\"\"b\"\"\"", "a\"\"\"b"),
+            ("'''$a \\${b}'''", "$a ${b}"),
+        ];
+
+        for (source, expected) in cases {
+            let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+            assert_eq!(tokens[0], new_token(TokenKind::String(expected.to_string()), source, 0), "{source}");
+        }
+
+        // Two quotes not followed by a third are an empty string.
+        let tokens = Scanner::new(b"''+\"\"").scan_tokens().unwrap();
+        assert_eq!(tokens.iter().map(|t| t.lexeme.as_str()).collect::<Vec<_>>(), ["''", "+", "\"\"", ""]);
+
+        // A multi-line string isn't interpolated, so each `${` in it is reported.
+        let errors = Scanner::new(b"'''a${b}\n${c}'''").scan_tokens().err().unwrap();
+        assert_eq!(errors, [4, 9].map(|offset| Error::InterpolationInMultiLineString {
+            snippet: Snippet::new(offset..offset + 2),
+        }));
+    }
+
+    #[test]
+    fn scanner_scan_tokens_escape() {
+        let cases = [
+            (r#"'a\nb'"#, "a\nb"),
+            (r#""\t\r\\""#, "\t\r\\"),
+            (r#"'\'\"'"#, "'\""),
+            (r#""\"\'""#, "\"'"),
+            (r#"'a\0'"#, "a\0"),
+            (r#"'\x41\x7f'"#, "A\x7f"),
+            (r#""\u{41}\u{AC00}\u{1F600}""#, "A가😀"),
+            (r#"'가\u{B098}'"#, "가나"),
+        ];
+
+        for (source, expected) in cases {
+            let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+            assert_eq!(tokens[0], new_token(TokenKind::String(expected.to_string()), source, 0), "{source}");
+        }
+    }
+
+    #[test]
+    fn scanner_scan_tokens_escape_error() {
+        let cases = [
+            (r#"  'a\qb'"#, r"\q", 4),
+            (r#"  "가\나""#, r"\나", 6),
+            (r#"  '\x4'"#, r"\x4", 3),
+            (r#"  '\x80'"#, r"\x80", 3),
+            (r#"  "\u41""#, r"\u", 3),
+            (r#"  "\u{}""#, r"\u{", 3),
+            (r#"  "\u{1234567}""#, r"\u{123456", 3),
+            (r#"  "\u{D800}""#, r"\u{D800}", 3),
+            (r#"  "\u{41""#, r"\u{41", 3),
+        ];
+
+        for (source, escape, offset) in cases {
+            let errors = Scanner::new(source.as_bytes()).scan_tokens().err().unwrap();
+            assert_eq!(errors, [Error::InvalidEscape {
+                snippet: Snippet::new(offset..offset + escape.len()),
+                escape: escape.to_string(),
+            }], "{source}");
+        }
+
+        // Every invalid escape sequence is reported, including the ones after another in the same string.
+        let source = r#"'\q${1}\w' "a\zb\y""#;
+        let errors = Scanner::new(source.as_bytes()).scan_tokens().err().unwrap();
+        assert_eq!(errors, [(1, r"\q"), (7, r"\w"), (13, r"\z"), (16, r"\y")].map(|(offset, escape)| {
+            Error::InvalidEscape {
+                snippet: Snippet::new(offset..offset + escape.len()),
+                escape: escape.to_string(),
+            }
+        }));
+    }
+
+    #[test]
+    fn scanner_scan_tokens_interpolation() {
+        use crate::token::TokenKind::*;
+
+        let source = r#"'a${b}c${ {"d${e}"} }\${f}'"#;
+        let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+        assert_eq!(tokens, [
+            new_token(StringPart("a".to_string()),      "'a",      0),
+            new_token(InterpolationStart,               "${",      2),
+            new_token(Identifier("b".to_string()),      "b",       4),
+            new_token(InterpolationEnd,                 "}",       5),
+            new_token(StringPart("c".to_string()),      "c",       6),
+            new_token(InterpolationStart,               "${",      7),
+            new_token(LeftBrace,                        "{",       10),
+            new_token(StringPart("d".to_string()),      "\"d",     11),
+            new_token(InterpolationStart,               "${",      13),
+            new_token(Identifier("e".to_string()),      "e",       15),
+            new_token(InterpolationEnd,                 "}",       16),
+            new_token(StringPart("".to_string()),       "\"",      17),
+            new_token(RightBrace,                       "}",       18),
+            new_token(InterpolationEnd,                 "}",       20),
+            new_token(StringPart("${f}".to_string()),   "\\${f}'", 21),
+            new_token(Eof,                              "",        27),
+        ]);
+    }
+
     #[test]
     fn scanner_scan_tokens_error() {
         let source = b"  &";
         let errors = Scanner::new(source).scan_tokens().err().unwrap();
         assert_eq!(errors.len(), 1);
         assert_eq!(errors[0], Error::UnexpectedChar {
-            snippet: Snippet::new(2),
+            snippet: Snippet::new(2..3),
             c: '&',
         });
 
@@ -370,28 +793,87 @@ mod tests {
         let errors = Scanner::new(&source).scan_tokens().err().unwrap();
         assert_eq!(errors.len(), 1);
         assert_eq!(errors[0], Error::InvalidUtf8Char {
-            snippet: Snippet::new(5),
+            snippet: Snippet::new(5..6),
         });
 
         let source = b"  /*a";
         let errors = Scanner::new(source).scan_tokens().err().unwrap();
         assert_eq!(errors.len(), 1);
         assert_eq!(errors[0], Error::UnterminatedMultiLineComment {
-            snippet: Snippet::new(2),
+            snippet: Snippet::new(2..5),
         });
 
         let source = b"  'a";
         let errors = Scanner::new(source).scan_tokens().err().unwrap();
         assert_eq!(errors.len(), 1);
         assert_eq!(errors[0], Error::UnterminatedSingleQuoteString {
-            snippet: Snippet::new(2),
+            snippet: Snippet::new(2..4),
         });
 
         let source = b"  \"a";
         let errors = Scanner::new(source).scan_tokens().err().unwrap();
         assert_eq!(errors.len(), 1);
         assert_eq!(errors[0], Error::UnterminatedDoubleQuoteString {
-            snippet: Snippet::new(2),
+            snippet: Snippet::new(2..4),
         });
+
+        let source = b"  'a${b}c";
+        let errors = Scanner::new(source).scan_tokens().err().unwrap();
+        assert_eq!(errors, [Error::UnterminatedSingleQuoteString {
+            snippet: Snippet::new(2..9),
+        }]);
+
+        let source = b"  'a${ {b} ";
+        let errors = Scanner::new(source).scan_tokens().err().unwrap();
+        assert_eq!(errors, [Error::UnterminatedInterpolation {
+            snippet: Snippet::new(4..11),
+        }]);
+
+        let source = b"  r##\"a\"#";
+        let errors = Scanner::new(source).scan_tokens().err().unwrap();
+        assert_eq!(errors, [Error::UnterminatedRawString {
+            snippet: Snippet::new(2..9),
+            hashes: 2,
+        }]);
+
+        let source = b"  '''a\n''";
+        let errors = Scanner::new(source).scan_tokens().err().unwrap();
+        assert_eq!(errors, [Error::UnterminatedMultiLineString {
+            snippet: Snippet::new(2..9),
+            quote: '\'',
+        }]);
+
+        let source = b"  \"\"\"\\\"\"\"";
+        let errors = Scanner::new(source).scan_tokens().err().unwrap();
+        assert_eq!(errors, [Error::UnterminatedMultiLineString {
+            snippet: Snippet::new(2..9),
+            quote: '"',
+        }]);
+
+        let source = b"  a$b";
+        let errors = Scanner::new(source).scan_tokens().err().unwrap();
+        assert_eq!(errors, [Error::UnexpectedChar {
+            snippet: Snippet::new(3..4),
+            c: '$',
+        }]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn scanner_scan_tokens_with_offset() {
+        use crate::token::TokenKind::*;
+
+        let tokens = Scanner::with_offset(b"print x;", 100).scan_tokens().unwrap();
+        assert_eq!(tokens, [
+            new_token(Print,                        "print",   100),
+            new_token(Identifier("x".to_string()),  "x",       106),
+            new_token(Semicolon,                    ";",       107),
+            new_token(Eof,                          "",        108),
+        ]);
+
+        let errors = Scanner::with_offset(b"  &", 100).scan_tokens().err().unwrap();
+        assert_eq!(errors, [Error::UnexpectedChar {
+            snippet: Snippet::new(102..103),
+            c: '&',
+        }]);
+    }
+}
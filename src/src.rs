@@ -1,17 +1,36 @@
 use std::fmt::{self, Display, Formatter};
 use std::iter;
-use std::ops::RangeInclusive;
-use text_colorizer::Colorize;
+use std::ops::{Range, RangeInclusive};
+use std::rc::Rc;
 use crate::utils::string::Substring;
 
 pub type Index = usize;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Position {
     pub line: Index,
     pub column: Index,
 }
 
+impl Position {
+    /// Finds the line and column of `offset` in `source`, both of which are 0-based,
+    /// and the column of which is counted in chars, not in bytes.
+    pub fn resolve(source: &[u8], offset: Index) -> Self {
+        let offset = offset.min(source.len());
+        let line_start = Self::line_start(source, offset);
+        Position {
+            line: source[..line_start].iter().filter(|&&c| c == b'\n').count(),
+            // The continuation bytes of UTF-8 are not counted, so that each char is counted once.
+            column: source[line_start..offset].iter().filter(|&&c| c & 0xC0 != 0x80).count(),
+        }
+    }
+
+    /// Finds the offset of the start of the line `offset` is in.
+    pub fn line_start(source: &[u8], offset: Index) -> Index {
+        source[..offset.min(source.len())].iter().rposition(|&c| c == b'\n').map_or(0, |i| i + 1)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Location {
     Created {
@@ -30,14 +49,8 @@ impl Location {
 
     pub fn resolve(&mut self, source: &[u8]) {
         if let Location::Created { offset } = self {
-            let mut line = 0;
-            let mut line_start = 0;
-            for i in 0..*offset  {
-                if source[i] == b'\n' {
-                    line += 1;
-                    line_start = i + 1;
-                }
-            }
+            let pos = Position::resolve(source, *offset);
+            let line_start = Position::line_start(source, *offset);
 
             let mut line_end = source.len() - 1;
             for i in *offset..source.len() {
@@ -55,25 +68,11 @@ impl Location {
             }
 
             *self = Location::Resolved {
-                pos: Position { line, column: *offset - line_start },
+                pos,
                 line: line_start..=line_end,
             };
         }
     }
-
-    pub fn snippet(&self, source: &[u8]) -> Option<String> {
-        match self {
-            Location::Created { .. } => None,
-            Location::Resolved { pos, line } => {
-                let pos_line = pos.line + 1;
-                let code = source.substring_lossy(line.clone());
-                let spaces = iter::repeat(' ')
-                    .take(pos_line.to_string().len() + 3 + pos.column)
-                    .collect::<String>();
-                Some(format!("{} | {code}\n{spaces}{}", pos_line, "^".red().bold()))
-            },
-        }
-    }
 }
 
 impl Display for Location {
@@ -89,62 +88,234 @@ impl Display for Location {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// The identifier of a file in a `SourceMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(usize);
+
+/// A source added to a `SourceMap`, whose code takes the offsets from `start` in it.
+#[derive(Debug)]
+pub struct SourceFile {
+    name: Rc<str>,
+    source: Rc<[u8]>,
+    start: Index,
+    /// The offsets the lines of the source start at in it, which are searched for the line of an offset.
+    line_starts: Vec<Index>,
+}
+
+impl SourceFile {
+    /// Returns the name of the file, which is its path, or a pseudo-name such as `<repl:1>`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &[u8] {
+        &self.source
+    }
+
+    /// Returns the offset the file starts at in the source map it's added to.
+    pub fn start(&self) -> Index {
+        self.start
+    }
+
+    /// Finds the 0-based line `offset` in the file is in, by a binary search of the starts of its lines.
+    pub fn line_of(&self, offset: Index) -> Index {
+        self.line_starts.partition_point(|&start| start <= offset) - 1
+    }
+}
+
+/// The sources loaded to run, each of which takes its own range of offsets, so that an offset found
+/// in an error tells both the file and the position in it, as it does in `rustc`.
+///
+/// The first file starts at offset 0, and each of the others starts one byte past the end of the previous one,
+/// where the end is the offset of the `Eof` token of a file.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap { files: Vec::new() }
+    }
+
+    /// Adds `source` named `name`, which must be scanned by `Scanner::with_offset` from the start of it.
+    pub fn add(&mut self, name: impl Into<Rc<str>>, source: impl Into<Rc<[u8]>>) -> FileId {
+        let start = self.files.last().map_or(0, |f| f.start + f.source.len() + 1);
+        let source = source.into();
+        let line_starts = iter::once(0)
+            .chain(source.iter().enumerate().filter(|&(_, &c)| c == b'\n').map(|(i, _)| i + 1))
+            .collect();
+        self.files.push(SourceFile {
+            name: name.into(),
+            source,
+            start,
+            line_starts,
+        });
+        FileId(self.files.len() - 1)
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    #[inline(always)]
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0]
+    }
+
+    /// Finds the file `offset` is in.
+    pub fn lookup(&self, offset: Index) -> Option<FileId> {
+        let i = self.files.partition_point(|f| f.start <= offset).checked_sub(1)?;
+        let file = &self.files[i];
+        (offset <= file.start + file.source.len()).then_some(FileId(i))
+    }
+}
+
+/// A piece of code an error is found at, which holds only its range in a `SourceMap` when it's created,
+/// and is resolved against the source map to find its file, position and lines before it's rendered.
+///
+/// The range doesn't tell which source map it's in, so every file that code is run from must be added
+/// to the one source map its errors are resolved against, as `Lox` and `Interpreter` each keep their own.
+/// A range resolved against another source map is found in the wrong file, or in no file at all.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Snippet {
+    range: Range<Index>,
+    /// Boxed to keep errors small, since most snippets are never resolved.
+    resolved: Option<Box<ResolvedSnippet>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ResolvedSnippet {
+    file: FileId,
+    name: Rc<str>,
     offset: Index,
-    code: Option<String>,
+    pos: Position,
+    end: Position,
+    lines: Vec<String>,
 }
 
 impl Snippet {
-    pub fn new(offset: Index) -> Self {
+    /// Creates a snippet of the code in `range`, which points at the code at its start if it's empty.
+    pub fn new(range: Range<Index>) -> Self {
         Snippet {
-            offset,
-            code: None,
+            range,
+            resolved: None,
         }
     }
 
-    pub fn resolve(&mut self, source: &[u8]) {
-        if let None = self.code {
-            let mut loc = Location::new(self.offset);
-            loc.resolve(source);
-            self.code = loc.snippet(source);
-        }
+    pub fn offset(&self) -> Index {
+        self.range.start
     }
-}
 
-impl Display for Snippet {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        if let Some(code) = &self.code {
-            write!(f, "{code}")
-        } else {
-            Ok(())
+    pub fn range(&self) -> Range<Index> {
+        self.range.clone()
+    }
+
+    /// Returns the file the range is in, if it's resolved.
+    pub fn file(&self) -> Option<FileId> {
+        self.resolved.as_ref().map(|r| r.file)
+    }
+
+    /// Returns the name of the file the range is in, if it's resolved.
+    pub fn file_name(&self) -> Option<&str> {
+        self.resolved.as_ref().map(|r| &*r.name)
+    }
+
+    /// Returns the offset of the start of the range in its file, if it's resolved.
+    pub fn file_offset(&self) -> Option<Index> {
+        self.resolved.as_ref().map(|r| r.offset)
+    }
+
+    /// Returns the position of the start of the range in its file, if it's resolved.
+    pub fn position(&self) -> Option<&Position> {
+        self.resolved.as_ref().map(|r| &r.pos)
+    }
+
+    /// Returns the position of the end of the range in its file, which is past its last char, if it's resolved.
+    pub fn end_position(&self) -> Option<&Position> {
+        self.resolved.as_ref().map(|r| &r.end)
+    }
+
+    /// Returns the line of code the range starts in without its line break, if it's resolved.
+    pub fn code(&self) -> Option<&str> {
+        self.resolved.as_ref().map(|r| r.lines[0].as_str())
+    }
+
+    /// Returns the lines of code the range is in without their line breaks, if it's resolved.
+    pub fn lines(&self) -> Option<&[String]> {
+        self.resolved.as_ref().map(|r| r.lines.as_slice())
+    }
+
+    pub fn resolve(&mut self, sources: &SourceMap) {
+        let Some(id) = sources.lookup(self.range.start).filter(|_| self.resolved.is_none()) else {
+            return;
+        };
+        let file = sources.file(id);
+        let source = file.source();
+        let start = self.range.start - file.start;
+        let end = (self.range.end - file.start).clamp(start, source.len());
+
+        // The lines end at the last char of the range, not counting the line breaks it ends with.
+        let mut last = end.saturating_sub(1).max(start);
+        while last > start && matches!(source.get(last), Some(b'\n' | b'\r')) {
+            last -= 1;
         }
+        let pos = Position::resolve(source, start);
+        let line_start = Position::line_start(source, start);
+        let line_end = source[last.min(source.len())..].iter()
+            .position(|&c| c == b'\n')
+            .map_or(source.len(), |i| last + i);
+        let lines = source.substring_lossy(line_start..line_end)
+            .split('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line).to_string())
+            .collect();
+
+        self.resolved = Some(Box::new(ResolvedSnippet {
+            file: id,
+            name: file.name.clone(),
+            offset: start,
+            pos,
+            end: Position::resolve(source, end),
+            lines,
+        }));
     }
 }
 
 pub trait ResolveSnippet {
-    fn resolve_snippet(&mut self, source: &[u8]);
+    fn resolve_snippet(&mut self, sources: &SourceMap);
+}
+
+impl ResolveSnippet for Snippet {
+    fn resolve_snippet(&mut self, sources: &SourceMap) {
+        self.resolve(sources);
+    }
 }
 
 impl<T: ResolveSnippet> ResolveSnippet for Vec<T> {
-    fn resolve_snippet(&mut self, source: &[u8]) {
+    fn resolve_snippet(&mut self, sources: &SourceMap) {
         for t in self {
-            t.resolve_snippet(source);
+            t.resolve_snippet(sources);
         }
     }
 }
 
 pub struct SnippetResolver<'a> {
-    source: &'a [u8],
+    sources: &'a SourceMap,
 }
 
 impl<'a> SnippetResolver<'a> {
-    pub fn new(source: &'a [u8]) -> Self {
-        SnippetResolver { source }
+    pub fn new(sources: &'a SourceMap) -> Self {
+        SnippetResolver { sources }
     }
 
     pub fn resolve<T: ResolveSnippet>(&self, mut t: T) -> T {
-        t.resolve_snippet(self.source);
+        t.resolve_snippet(self.sources);
         t
     }
 }
@@ -185,4 +356,49 @@ mod tests {
             assert_eq!(loc, expected);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn position_resolve_unicode() {
+        let source = "print '가나';\nvar 다 = 'é';".as_bytes();
+        let cases = [
+            ("'가", 0, 6),
+            ("나", 0, 8),
+            ("';\n", 0, 9),
+            ("다", 1, 4),
+            ("=", 1, 6),
+            ("é';", 1, 9),
+        ];
+
+        for (s, line, column) in cases {
+            let offset = source.windows(s.len()).position(|w| w == s.as_bytes()).unwrap();
+            assert_eq!(Position::resolve(source, offset), Position { line, column }, "{s}");
+        }
+    }
+
+    #[test]
+    fn snippet_resolve() {
+        let mut sources = SourceMap::new();
+        let a = sources.add("a.lox", b"print 1;".as_slice());
+        let b = sources.add("<repl:2>", b"var x;\nprint x @;".as_slice());
+        assert_eq!((sources.file(a).start(), sources.file(b).start()), (0, 9));
+        assert_eq!(sources.lookup(8), Some(a));
+        assert_eq!(sources.lookup(9), Some(b));
+        assert_eq!(sources.lookup(26), Some(b));
+        assert_eq!(sources.lookup(27), None);
+        assert_eq!([0, 5, 6, 7, 17].map(|offset| sources.file(b).line_of(offset)), [0, 0, 0, 1, 1]);
+
+        let mut snippet = Snippet::new(24..25);
+        snippet.resolve(&sources);
+        assert_eq!(snippet.file(), Some(b));
+        assert_eq!(snippet.file_name(), Some("<repl:2>"));
+        assert_eq!(snippet.position(), Some(&Position { line: 1, column: 8 }));
+        assert_eq!(snippet.end_position(), Some(&Position { line: 1, column: 9 }));
+        assert_eq!(snippet.code(), Some("print x @;"));
+
+        let mut snippet = Snippet::new(13..27);
+        snippet.resolve(&sources);
+        assert_eq!(snippet.position(), Some(&Position { line: 0, column: 4 }));
+        assert_eq!(snippet.end_position(), Some(&Position { line: 1, column: 10 }));
+        assert_eq!(snippet.lines(), Some(["var x;".to_string(), "print x @;".to_string()].as_slice()));
+    }
+}
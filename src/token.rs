@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
 use lazy_static::lazy_static;
 use crate::src::Index;
 use crate::types::Number;
@@ -26,6 +27,7 @@ lazy_static! {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum TokenKind {
     // Single-character tokens
     LeftParen,
@@ -54,6 +56,12 @@ pub enum TokenKind {
     String(String),
     Number(Number),
 
+    // String interpolation, such as `"a${b}c"` scanned as `StringPart("a")`, `InterpolationStart`, `b`,
+    // `InterpolationEnd` and `StringPart("c")`.
+    StringPart(String),
+    InterpolationStart,
+    InterpolationEnd,
+
     // Identifier
     Identifier(String),
 
@@ -104,6 +112,9 @@ impl Display for TokenKind {
             LessEqual => f.write_str("<="),
             String(s) => s.fmt(f),
             Number(n) => n.fmt(f),
+            StringPart(s) => s.fmt(f),
+            InterpolationStart => f.write_str("${"),
+            InterpolationEnd => f.write_str("}"),
             Identifier(s) => f.write_str(s),
             And => f.write_str("and"),
             Class => f.write_str("class"),
@@ -133,6 +144,14 @@ pub struct Token {
     pub offset: Index,
 }
 
+impl Token {
+    /// Returns the range of the code of the token, which is its lexeme.
+    #[inline(always)]
+    pub fn range(&self) -> Range<Index> {
+        self.offset..self.offset + self.lexeme.len()
+    }
+}
+
 impl Display for Token {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         self.kind.fmt(f)
@@ -35,7 +35,7 @@ impl<Rng: IntoRange<usize>> Substring<Rng> for [u8] {
     }
 
     fn substring_lossy(&self, range: Rng) -> String {
-        String::from_utf8_lossy(&self[range.into_range(0..self.len())].to_vec()).to_string()
+        String::from_utf8_lossy(&self[range.into_range(0..self.len())]).to_string()
     }
 }
 
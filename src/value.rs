@@ -0,0 +1,342 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::ptr;
+use std::rc::Rc;
+use crate::ast;
+use crate::environment::Environment;
+use crate::gc::{Heap, Trace, Tracer};
+use crate::host::HostObject;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::src::Snippet;
+use crate::token::Token;
+use crate::types::Number;
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(Number),
+    String(Rc<str>),
+    Callable(Rc<dyn Callable>),
+    Instance(Rc<RefCell<Instance>>),
+    /// An instance of a Rust struct exposed to Lox.
+    Host(Rc<dyn HostObject>),
+}
+
+impl Value {
+    /// Returns whether the value counts as true in a condition: `nil` and `false` are falsey,
+    /// and everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Callable(_) => "callable",
+            Value::Instance(_) | Value::Host(_) => "instance",
+        }
+    }
+}
+
+/// Values of different types are never equal, and callables and instances are equal
+/// only to themselves.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Callable(a), Value::Callable(b)) => ptr::addr_eq(Rc::as_ptr(a), Rc::as_ptr(b)),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            (Value::Host(a), Value::Host(b)) => ptr::addr_eq(Rc::as_ptr(a), Rc::as_ptr(b)),
+            _ => false,
+        }
+    }
+}
+
+impl Trace for Value {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Value::Callable(callable) => tracer.visit(callable),
+            Value::Instance(instance) => tracer.visit(instance),
+            // Instances of Rust structs aren't traced, so the values they hold are kept alive as roots.
+            Value::Nil | Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Host(_) => {},
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => f.write_str("nil"),
+            Value::Bool(b) => b.fmt(f),
+            Value::Number(n) => n.fmt(f),
+            Value::String(s) => f.write_str(s),
+            Value::Callable(c) => c.fmt(f),
+            Value::Instance(i) => i.borrow().fmt(f),
+            Value::Host(h) => h.fmt(f),
+        }
+    }
+}
+
+/// A value which can be called with arguments, downcastable into its concrete type through `Any`.
+pub trait Callable: Any + fmt::Debug + Display {
+    fn arity(&self) -> usize;
+
+    /// Calls this with `arguments`, where `paren` is the closing parenthesis of the call.
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError>;
+}
+
+/// A user-defined function, which closes over the environment it's declared in.
+pub struct Function {
+    declaration: ast::Function,
+    closure: Rc<RefCell<Environment>>,
+    is_initializer: bool,
+}
+
+impl Function {
+    pub fn new(declaration: ast::Function, closure: Rc<RefCell<Environment>>, is_initializer: bool) -> Self {
+        Function { declaration, closure, is_initializer }
+    }
+
+    /// Returns a copy of this method whose `this` is bound to `instance`.
+    pub fn bind(&self, instance: Rc<RefCell<Instance>>, heap: &mut Heap) -> Rc<Function> {
+        let mut environment = Environment::with_enclosing(self.closure.clone());
+        environment.define("this", Value::Instance(instance));
+        let environment = heap.alloc_cell(environment);
+        heap.alloc(Function::new(self.declaration.clone(), environment, self.is_initializer))
+    }
+
+    fn this(&self) -> Value {
+        self.closure.borrow().get_own("this").unwrap_or(Value::Nil)
+    }
+}
+
+impl Callable for Function {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        _: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let mut environment = Environment::with_enclosing(self.closure.clone());
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(&param.lexeme, argument);
+        }
+
+        let value = interpreter.execute_body(&self.declaration.body, environment)?;
+        if self.is_initializer {
+            Ok(self.this())
+        } else {
+            Ok(value)
+        }
+    }
+}
+
+impl Trace for Function {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.visit(&self.closure);
+    }
+}
+
+/// Doesn't print the closure, which may contain the function itself.
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.declaration.name.lexeme)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
+
+/// The Rust function behind a native function, which fails with an error message.
+pub type NativeFn = dyn Fn(&[Value]) -> Result<Value, Box<dyn Error>>;
+
+/// A function implemented in Rust, and defined by the program embedding Lox.
+pub struct NativeFunction {
+    name: String,
+    arity: usize,
+    function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(name: String, arity: usize, function: Box<NativeFn>) -> Self {
+        NativeFunction { name, arity, function }
+    }
+}
+
+impl Callable for NativeFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(self: Rc<Self>, _: &mut Interpreter, paren: &Token, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        (self.function)(&arguments).map_err(|e| RuntimeError::Native {
+            snippet: Snippet::new(paren.range()),
+            name: self.name.clone(),
+            message: e.to_string(),
+        })
+    }
+}
+
+/// Values captured by the Rust function can't be traced, so they're kept alive as roots.
+impl Trace for NativeFunction {
+    fn trace(&self, _: &mut Tracer) {}
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for NativeFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+#[derive(Debug)]
+pub struct Class {
+    name: String,
+    superclass: Option<Rc<Class>>,
+    methods: HashMap<String, Rc<Function>>,
+}
+
+impl Class {
+    pub fn new(name: String, superclass: Option<Rc<Class>>, methods: HashMap<String, Rc<Function>>) -> Self {
+        Class { name, superclass, methods }
+    }
+
+    /// Looks a method up in this class, and then in its superclasses.
+    pub fn find_method(&self, name: &str) -> Option<Rc<Function>> {
+        self.methods.get(name).cloned().or_else(|| {
+            self.superclass.as_ref().and_then(|s| s.find_method(name))
+        })
+    }
+}
+
+impl Callable for Class {
+    fn arity(&self) -> usize {
+        self.find_method("init").map_or(0, |init| init.arity())
+    }
+
+    /// Creates an instance, and runs the initializer on it if any.
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let instance = interpreter.heap().alloc_cell(Instance::new(self.clone()));
+        if let Some(init) = self.find_method("init") {
+            init.bind(instance.clone(), interpreter.heap()).call(interpreter, paren, arguments)?;
+        }
+        Ok(Value::Instance(instance))
+    }
+}
+
+impl Trace for Class {
+    fn trace(&self, tracer: &mut Tracer) {
+        if let Some(superclass) = &self.superclass {
+            tracer.visit(superclass);
+        }
+        for method in self.methods.values() {
+            tracer.visit(method);
+        }
+    }
+}
+
+impl Display for Class {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+pub struct Instance {
+    class: Rc<Class>,
+    fields: HashMap<String, Value>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Self {
+        Instance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Looks a property up in `instance`, where fields shadow methods, and methods are bound to it.
+    pub fn get(instance: &Rc<RefCell<Instance>>, name: &Token, heap: &mut Heap) -> Result<Value, RuntimeError> {
+        if let Some(value) = instance.borrow().fields.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+
+        let method = instance.borrow().class.find_method(&name.lexeme);
+        match method {
+            Some(method) => Ok(Value::Callable(method.bind(instance.clone(), heap))),
+            None => Err(RuntimeError::UndefinedProperty {
+                snippet: Snippet::new(name.range()),
+                name: name.lexeme.clone(),
+            }),
+        }
+    }
+
+    pub fn set(&mut self, name: &Token, value: Value) {
+        self.fields.insert(name.lexeme.clone(), value);
+    }
+}
+
+impl Trace for Instance {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.visit(&self.class);
+        for value in self.fields.values() {
+            value.trace(tracer);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.fields.clear();
+    }
+}
+
+/// Doesn't print the fields, which may contain the instance itself.
+impl fmt::Debug for Instance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Instance")
+            .field("class", &self.class.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for Instance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
@@ -0,0 +1,233 @@
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+use std::rc::Rc;
+use crate::src::Index;
+use crate::types::Number;
+use crate::vm::value::Function;
+
+macro_rules! opcodes {
+    ($($(#[$attr:meta])* $name:ident),* $(,)?) => {
+        /// An instruction of the VM, encoded as a single byte followed by its operands, if any.
+        #[repr(u8)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum OpCode {
+            $($(#[$attr])* $name),*
+        }
+
+        impl OpCode {
+            const ALL: &[OpCode] = &[$(OpCode::$name),*];
+        }
+    };
+}
+
+opcodes! {
+    /// Pushes the constant at a 2-byte index.
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    /// Pushes the local variable at a 1-byte slot of the current frame.
+    GetLocal,
+    SetLocal,
+    /// Pushes the global variable named by the constant at a 2-byte index.
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    /// Pushes the variable captured at a 1-byte index of the current closure.
+    GetUpvalue,
+    SetUpvalue,
+    /// Replaces an instance with its property named by the constant at a 2-byte index.
+    GetProperty,
+    SetProperty,
+    /// Replaces `this` and a superclass with the superclass method named by the constant at a 2-byte index.
+    GetSuper,
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    /// Replaces a value with the string it's printed as, for string interpolation.
+    Stringify,
+    Print,
+    /// Jumps forwards by a 2-byte distance.
+    Jump,
+    /// Jumps forwards by a 2-byte distance if the top of the stack is falsey, without popping it.
+    JumpIfFalse,
+    /// Jumps backwards by a 2-byte distance.
+    Loop,
+    /// Calls a callee under a 1-byte number of arguments.
+    Call,
+    /// Creates a closure of the function constant at a 2-byte index, followed by a pair of bytes
+    /// for each variable it captures: whether it's a local of the enclosing function, and its index.
+    Closure,
+    CloseUpvalue,
+    Return,
+    /// Creates a class named by the constant at a 2-byte index.
+    Class,
+    /// Copies the methods of a superclass into the subclass above it.
+    Inherit,
+    /// Adds a closure to the class below it, as a method named by the constant at a 2-byte index.
+    Method,
+}
+
+/// The kinds of operands following an opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    None,
+    /// A 1-byte slot, index or number of arguments.
+    Byte,
+    /// A 2-byte index into the constant pool.
+    Constant,
+    /// A 2-byte jump distance.
+    Jump,
+    /// A 2-byte index of a function constant, followed by a pair of bytes for each variable it captures.
+    Closure,
+}
+
+impl OpCode {
+    pub fn operand(self) -> Operand {
+        use OpCode::*;
+        match self {
+            Constant | GetGlobal | DefineGlobal | SetGlobal | GetProperty | SetProperty | GetSuper | Class
+            | Method => Operand::Constant,
+            GetLocal | SetLocal | GetUpvalue | SetUpvalue | Call => Operand::Byte,
+            Jump | JumpIfFalse | Loop => Operand::Jump,
+            Closure => Operand::Closure,
+            Nil | True | False | Pop | Equal | Greater | GreaterEqual | Less | LessEqual | Add | Subtract
+            | Multiply | Divide | Not | Negate | Stringify | Print | CloseUpvalue | Return | Inherit => Operand::None,
+        }
+    }
+}
+
+impl TryFrom<u8> for OpCode {
+    type Error = u8;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        OpCode::ALL.get(byte as usize).copied().ok_or(byte)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Constant {
+    Number(Number),
+    String(Rc<str>),
+    Function(Rc<Function>),
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Constant::Number(n) => n.fmt(f),
+            Constant::String(s) => f.write_str(s),
+            Constant::Function(function) => function.fmt(f),
+        }
+    }
+}
+
+/// A sequence of bytecode together with the constants it refers to.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    code: Vec<u8>,
+    constants: Vec<Constant>,
+    /// The line table, which maps every byte of `code` to the source range of the token it's compiled from,
+    /// run-length encoded as pairs of a range and the number of consecutive bytes compiled from it.
+    spans: Vec<(Range<Index>, usize)>,
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a chunk from its parts, where `spans` must be the run-length encoded line table
+    /// as returned by `Chunk::spans`.
+    pub fn from_parts(code: Vec<u8>, constants: Vec<Constant>, spans: Vec<(Range<Index>, usize)>) -> Self {
+        Chunk { code, constants, spans }
+    }
+
+    #[inline(always)]
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    #[inline(always)]
+    pub fn constants(&self) -> &[Constant] {
+        &self.constants
+    }
+
+    #[inline(always)]
+    pub fn spans(&self) -> &[(Range<Index>, usize)] {
+        &self.spans
+    }
+
+    pub fn write(&mut self, byte: u8, span: Range<Index>) {
+        self.code.push(byte);
+        match self.spans.last_mut() {
+            Some((last, count)) if *last == span => *count += 1,
+            _ => self.spans.push((span, 1)),
+        }
+    }
+
+    /// Overwrites the byte at `index`, which must have already been written.
+    pub fn patch(&mut self, index: usize, byte: u8) {
+        self.code[index] = byte;
+    }
+
+    /// Appends `constant` to the constant pool, and returns its index.
+    pub fn add_constant(&mut self, constant: Constant) -> usize {
+        self.constants.push(constant);
+        self.constants.len() - 1
+    }
+
+    /// Returns the source range which the byte at `index` is compiled from.
+    pub fn span_at(&self, index: usize) -> Range<Index> {
+        let mut start = 0;
+        for (span, count) in &self.spans {
+            start += count;
+            if index < start {
+                return span.clone();
+            }
+        }
+        panic!("byte index {index} out of the chunk of {} bytes", self.code.len());
+    }
+
+    /// Returns the source range of the last byte written, if any.
+    pub fn last_span(&self) -> Option<Range<Index>> {
+        self.spans.last().map(|(span, _)| span.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_span_at() {
+        let mut chunk = Chunk::new();
+        chunk.write(OpCode::Constant as u8, 3..5);
+        chunk.write(0, 3..5);
+        chunk.write(0, 3..5);
+        chunk.write(OpCode::Negate as u8, 2..3);
+        chunk.write(OpCode::Print as u8, 2..3);
+        chunk.write(OpCode::Return as u8, 2..9);
+
+        assert_eq!(chunk.spans, [(3..5, 3), (2..3, 2), (2..9, 1)]);
+        let spans = (0..chunk.code().len()).map(|i| chunk.span_at(i)).collect::<Vec<_>>();
+        assert_eq!(spans, [3..5, 3..5, 3..5, 2..3, 2..3, 2..9]);
+        assert_eq!(chunk.last_span(), Some(2..9));
+    }
+
+    #[test]
+    fn opcode_try_from() {
+        assert_eq!(OpCode::try_from(OpCode::Constant as u8), Ok(OpCode::Constant));
+        assert_eq!(OpCode::try_from(OpCode::Method as u8), Ok(OpCode::Method));
+        assert_eq!(OpCode::try_from(OpCode::Method as u8 + 1), Err(OpCode::Method as u8 + 1));
+    }
+}
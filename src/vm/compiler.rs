@@ -0,0 +1,682 @@
+use std::mem;
+use std::ops::Range;
+use std::rc::Rc;
+use thiserror::Error;
+use qlox_macros::ResolveSnippet;
+use crate::ast::{
+    self, Accept, Assign, Binary, Block, Call, Class, Expr, ExprVisitor, Expression, Get, Grouping, If,
+    Literal, LiteralValue, Logical, Print, Return, Set, Stmt, StmtVisitor, Super, This, Unary, Var,
+    Variable, While,
+};
+use crate::consts::limit::{MAX_CONSTANTS, MAX_JUMP, MAX_LOCALS, MAX_UPVALUES};
+use crate::src::{Index, Snippet};
+use crate::token::TokenKind;
+use crate::vm::chunk::{Chunk, Constant, OpCode};
+use crate::vm::value::Function;
+
+#[derive(Error, Debug, ResolveSnippet, PartialEq)]
+pub enum Error {
+    #[error("can't have more than {MAX_LOCALS} local variables in one function")]
+    TooManyLocals {
+        snippet: Snippet,
+    },
+
+    #[error("can't have more than {MAX_UPVALUES} closure variables in one function")]
+    TooManyUpvalues {
+        snippet: Snippet,
+    },
+
+    #[error("can't have more than {MAX_CONSTANTS} constants in one function")]
+    TooManyConstants {
+        snippet: Snippet,
+    },
+
+    #[error("can't jump over more than {MAX_JUMP} bytes of code")]
+    JumpTooLarge {
+        snippet: Snippet,
+    },
+}
+
+impl Error {
+    pub fn snippet(&self) -> &Snippet {
+        match self {
+            Error::TooManyLocals { snippet, .. } |
+            Error::TooManyUpvalues { snippet, .. } |
+            Error::TooManyConstants { snippet, .. } |
+            Error::JumpTooLarge { snippet, .. } => snippet,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Initializer,
+    Method,
+}
+
+/// A local variable, which lives in a stack slot of the function it's declared in.
+struct Local {
+    name: String,
+    depth: usize,
+    /// Whether the variable is captured by a closure, and so must be moved into the heap
+    /// when it goes out of scope.
+    is_captured: bool,
+}
+
+/// A variable captured by a closure, which is either a local of the enclosing function
+/// or one of the variables captured by the enclosing function.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Capture {
+    index: u8,
+    is_local: bool,
+}
+
+/// The state of a function being compiled.
+struct FunctionScope {
+    function: Function,
+    kind: FunctionKind,
+    /// The source range of the name of the function, for the code not compiled from any token.
+    span: Range<Index>,
+    locals: Vec<Local>,
+    captures: Vec<Capture>,
+    depth: usize,
+}
+
+/// Compiles a resolved program into the bytecode of its top-level script function,
+/// in a single pass over its syntax tree.
+pub struct Compiler {
+    /// The functions being compiled, where the innermost one is the last.
+    functions: Vec<FunctionScope>,
+    errors: Vec<Error>,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Compiler {
+            functions: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn compile(&mut self, stmts: &[Stmt]) -> Result<Rc<Function>, Vec<Error>> {
+        self.begin_function("", FunctionKind::Script, 0..0);
+        self.compile_stmts(stmts);
+        let (function, _) = self.end_function();
+        if !self.errors.is_empty() {
+            return Err(mem::take(&mut self.errors));
+        }
+        Ok(Rc::new(function))
+    }
+
+    fn compile_stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            stmt.accept(&mut *self, ());
+        }
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) {
+        expr.accept(self, ());
+    }
+
+    fn compile_function(&mut self, function: &ast::Function, kind: FunctionKind) {
+        let name = &function.name;
+        self.begin_function(&name.lexeme, kind, name.range());
+        self.begin_scope();
+        self.current().function.arity = function.params.len();
+        for param in &function.params {
+            self.add_local(&param.lexeme, param.range());
+        }
+        self.compile_stmts(&function.body);
+        let (function, captures) = self.end_function();
+
+        let constant = self.make_constant(Constant::Function(Rc::new(function)), name.range());
+        self.emit_op_u16(OpCode::Closure, constant, name.range());
+        for capture in captures {
+            self.emit_u8(capture.is_local as u8, name.range());
+            self.emit_u8(capture.index, name.range());
+        }
+    }
+
+    fn begin_function(&mut self, name: &str, kind: FunctionKind, span: Range<Index>) {
+        self.functions.push(FunctionScope {
+            function: Function {
+                name: name.into(),
+                arity: 0,
+                upvalue_count: 0,
+                chunk: Chunk::new(),
+            },
+            kind,
+            span: span.clone(),
+            locals: Vec::new(),
+            captures: Vec::new(),
+            depth: 0,
+        });
+
+        // The first slot holds the callee, which is the receiver bound to `this` in a method.
+        let this = match kind {
+            FunctionKind::Initializer | FunctionKind::Method => "this",
+            FunctionKind::Script | FunctionKind::Function => "",
+        };
+        self.add_local(this, span);
+    }
+
+    /// Finishes the innermost function with an implicit return, and returns it together with
+    /// the variables it captures.
+    fn end_function(&mut self) -> (Function, Vec<Capture>) {
+        self.emit_return(self.last_span());
+        let mut scope = self.functions.pop().expect("function must have begun");
+        scope.function.upvalue_count = scope.captures.len();
+        (scope.function, scope.captures)
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().depth += 1;
+    }
+
+    /// Ends the innermost scope, and discards its local variables from the stack.
+    fn end_scope(&mut self) {
+        self.current().depth -= 1;
+        let depth = self.current_depth();
+        let span = self.last_span();
+        while let Some(local) = self.current().locals.pop_if(|l| l.depth > depth) {
+            let op = if local.is_captured { OpCode::CloseUpvalue } else { OpCode::Pop };
+            self.emit_op(op, span.clone());
+        }
+    }
+
+    /// Declares a variable in the current scope, where its value must already be on the top of the stack
+    /// if it's local, or defines it if it's global.
+    fn define_variable(&mut self, name: &str, span: Range<Index>) {
+        if self.current_depth() > 0 {
+            self.add_local(name, span);
+        } else {
+            let constant = self.identifier_constant(name, span.clone());
+            self.emit_op_u16(OpCode::DefineGlobal, constant, span);
+        }
+    }
+
+    fn add_local(&mut self, name: &str, span: Range<Index>) {
+        if self.current().locals.len() == MAX_LOCALS {
+            self.errors.push(Error::TooManyLocals {
+                snippet: Snippet::new(span),
+            });
+            return;
+        }
+
+        let depth = self.current_depth();
+        self.current().locals.push(Local {
+            name: name.to_string(),
+            depth,
+            is_captured: false,
+        });
+    }
+
+    /// Emits the code to get or set a variable, which is looked up in the local scopes,
+    /// then in the enclosing functions, and then in the global scope.
+    fn emit_variable(&mut self, name: &str, span: Range<Index>, assign: bool) {
+        let level = self.functions.len() - 1;
+        let (op, operand) = if let Some(slot) = self.resolve_local(level, name) {
+            (if assign { OpCode::SetLocal } else { OpCode::GetLocal }, slot)
+        } else if let Some(index) = self.resolve_capture(level, name, span.clone()) {
+            (if assign { OpCode::SetUpvalue } else { OpCode::GetUpvalue }, index)
+        } else {
+            let constant = self.identifier_constant(name, span.clone());
+            let op = if assign { OpCode::SetGlobal } else { OpCode::GetGlobal };
+            self.emit_op_u16(op, constant, span);
+            return;
+        };
+        self.emit_op_u8(op, operand, span);
+    }
+
+    fn resolve_local(&self, level: usize, name: &str) -> Option<u8> {
+        self.functions[level].locals.iter().rposition(|l| l.name == name).map(|slot| slot as u8)
+    }
+
+    /// Looks a variable up in the functions enclosing the one at `level`, and captures it
+    /// through every function in between.
+    fn resolve_capture(&mut self, level: usize, name: &str, span: Range<Index>) -> Option<u8> {
+        if level == 0 {
+            return None;
+        }
+
+        if let Some(slot) = self.resolve_local(level - 1, name) {
+            self.functions[level - 1].locals[slot as usize].is_captured = true;
+            return Some(self.add_capture(level, Capture { index: slot, is_local: true }, span));
+        }
+
+        let index = self.resolve_capture(level - 1, name, span.clone())?;
+        Some(self.add_capture(level, Capture { index, is_local: false }, span))
+    }
+
+    fn add_capture(&mut self, level: usize, capture: Capture, span: Range<Index>) -> u8 {
+        let captures = &mut self.functions[level].captures;
+        if let Some(index) = captures.iter().position(|&c| c == capture) {
+            return index as u8;
+        }
+
+        if captures.len() == MAX_UPVALUES {
+            self.errors.push(Error::TooManyUpvalues {
+                snippet: Snippet::new(span),
+            });
+            return 0;
+        }
+
+        captures.push(capture);
+        (captures.len() - 1) as u8
+    }
+
+    fn make_constant(&mut self, constant: Constant, span: Range<Index>) -> u16 {
+        if self.chunk().constants().len() == MAX_CONSTANTS {
+            self.errors.push(Error::TooManyConstants {
+                snippet: Snippet::new(span),
+            });
+            return 0;
+        }
+        self.current().function.chunk.add_constant(constant) as u16
+    }
+
+    fn identifier_constant(&mut self, name: &str, span: Range<Index>) -> u16 {
+        self.make_constant(Constant::String(name.into()), span)
+    }
+
+    fn emit_return(&mut self, span: Range<Index>) {
+        if self.current().kind == FunctionKind::Initializer {
+            self.emit_op_u8(OpCode::GetLocal, 0, span.clone());
+        } else {
+            self.emit_op(OpCode::Nil, span.clone());
+        }
+        self.emit_op(OpCode::Return, span);
+    }
+
+    /// Emits a jump with a placeholder distance, and returns the index of the distance to patch.
+    fn emit_jump(&mut self, op: OpCode, span: Range<Index>) -> usize {
+        self.emit_op_u16(op, u16::MAX, span);
+        self.chunk().code().len() - 2
+    }
+
+    /// Patches the distance of the jump at `index` to land on the next instruction.
+    fn patch_jump(&mut self, index: usize) {
+        let distance = self.chunk().code().len() - index - 2;
+        if distance > MAX_JUMP {
+            self.errors.push(Error::JumpTooLarge {
+                snippet: Snippet::new(self.chunk().span_at(index)),
+            });
+        }
+
+        let [high, low] = (distance as u16).to_be_bytes();
+        let chunk = &mut self.current().function.chunk;
+        chunk.patch(index, high);
+        chunk.patch(index + 1, low);
+    }
+
+    fn emit_loop(&mut self, start: usize, span: Range<Index>) {
+        self.emit_op(OpCode::Loop, span.clone());
+        let distance = self.chunk().code().len() - start + 2;
+        if distance > MAX_JUMP {
+            self.errors.push(Error::JumpTooLarge {
+                snippet: Snippet::new(span.clone()),
+            });
+        }
+        self.emit_u16(distance as u16, span);
+    }
+
+    #[inline(always)]
+    fn emit_op(&mut self, op: OpCode, span: Range<Index>) {
+        self.emit_u8(op as u8, span);
+    }
+
+    #[inline(always)]
+    fn emit_op_u8(&mut self, op: OpCode, operand: u8, span: Range<Index>) {
+        self.emit_op(op, span.clone());
+        self.emit_u8(operand, span);
+    }
+
+    #[inline(always)]
+    fn emit_op_u16(&mut self, op: OpCode, operand: u16, span: Range<Index>) {
+        self.emit_op(op, span.clone());
+        self.emit_u16(operand, span);
+    }
+
+    #[inline(always)]
+    fn emit_u8(&mut self, byte: u8, span: Range<Index>) {
+        self.current().function.chunk.write(byte, span);
+    }
+
+    #[inline(always)]
+    fn emit_u16(&mut self, operand: u16, span: Range<Index>) {
+        for byte in operand.to_be_bytes() {
+            self.emit_u8(byte, span.clone());
+        }
+    }
+
+    #[inline(always)]
+    fn current(&mut self) -> &mut FunctionScope {
+        self.functions.last_mut().expect("function must have begun")
+    }
+
+    #[inline(always)]
+    fn current_depth(&self) -> usize {
+        self.functions.last().expect("function must have begun").depth
+    }
+
+    #[inline(always)]
+    fn chunk(&self) -> &Chunk {
+        &self.functions.last().expect("function must have begun").function.chunk
+    }
+
+    /// Returns the source range for the code which isn't compiled from a token of its own,
+    /// such as the one to pop the value of an expression statement.
+    #[inline(always)]
+    fn last_span(&self) -> Range<Index> {
+        let scope = self.functions.last().expect("function must have begun");
+        scope.function.chunk.last_span().unwrap_or_else(|| scope.span.clone())
+    }
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExprVisitor<(), ()> for &mut Compiler {
+    fn visit_assign(self, expr: &Assign, _: ()) {
+        self.compile_expr(&expr.value);
+        self.emit_variable(&expr.name.lexeme, expr.name.range(), true);
+    }
+
+    fn visit_binary(self, expr: &Binary, _: ()) {
+        use TokenKind::*;
+        self.compile_expr(&expr.left);
+        self.compile_expr(&expr.right);
+        let span = expr.operator.range();
+        match expr.operator.kind {
+            EqualEqual => self.emit_op(OpCode::Equal, span.clone()),
+            BangEqual => {
+                self.emit_op(OpCode::Equal, span.clone());
+                self.emit_op(OpCode::Not, span.clone());
+            },
+            Greater => self.emit_op(OpCode::Greater, span.clone()),
+            GreaterEqual => self.emit_op(OpCode::GreaterEqual, span.clone()),
+            Less => self.emit_op(OpCode::Less, span.clone()),
+            LessEqual => self.emit_op(OpCode::LessEqual, span.clone()),
+            Plus => self.emit_op(OpCode::Add, span.clone()),
+            Minus => self.emit_op(OpCode::Subtract, span.clone()),
+            Star => self.emit_op(OpCode::Multiply, span.clone()),
+            Slash => self.emit_op(OpCode::Divide, span.clone()),
+            _ => unreachable!("invalid binary operator `{}`", expr.operator),
+        }
+    }
+
+    fn visit_call(self, expr: &Call, _: ()) {
+        self.compile_expr(&expr.callee);
+        for argument in &expr.arguments {
+            self.compile_expr(argument);
+        }
+        self.emit_op_u8(OpCode::Call, expr.arguments.len() as u8, expr.paren.range());
+    }
+
+    fn visit_get(self, expr: &Get, _: ()) {
+        self.compile_expr(&expr.object);
+        let constant = self.identifier_constant(&expr.name.lexeme, expr.name.range());
+        self.emit_op_u16(OpCode::GetProperty, constant, expr.name.range());
+    }
+
+    fn visit_grouping(self, expr: &Grouping, _: ()) {
+        self.compile_expr(&expr.expr);
+    }
+
+    fn visit_literal(self, expr: &Literal, _: ()) {
+        let span = expr.token.range();
+        match &expr.value {
+            LiteralValue::Number(n) => {
+                let constant = self.make_constant(Constant::Number(*n), span.clone());
+                self.emit_op_u16(OpCode::Constant, constant, span.clone());
+            },
+            LiteralValue::String(s) => {
+                let constant = self.make_constant(Constant::String(s.as_str().into()), span.clone());
+                self.emit_op_u16(OpCode::Constant, constant, span.clone());
+            },
+            LiteralValue::True => self.emit_op(OpCode::True, span.clone()),
+            LiteralValue::False => self.emit_op(OpCode::False, span.clone()),
+            LiteralValue::Nil => self.emit_op(OpCode::Nil, span.clone()),
+        }
+    }
+
+    fn visit_logical(self, expr: &Logical, _: ()) {
+        self.compile_expr(&expr.left);
+        let span = expr.operator.range();
+        match expr.operator.kind {
+            TokenKind::And => {
+                let end_jump = self.emit_jump(OpCode::JumpIfFalse, span.clone());
+                self.emit_op(OpCode::Pop, span.clone());
+                self.compile_expr(&expr.right);
+                self.patch_jump(end_jump);
+            },
+            TokenKind::Or => {
+                let else_jump = self.emit_jump(OpCode::JumpIfFalse, span.clone());
+                let end_jump = self.emit_jump(OpCode::Jump, span.clone());
+                self.patch_jump(else_jump);
+                self.emit_op(OpCode::Pop, span.clone());
+                self.compile_expr(&expr.right);
+                self.patch_jump(end_jump);
+            },
+            _ => unreachable!("invalid logical operator `{}`", expr.operator),
+        }
+    }
+
+    fn visit_set(self, expr: &Set, _: ()) {
+        self.compile_expr(&expr.object);
+        self.compile_expr(&expr.value);
+        let constant = self.identifier_constant(&expr.name.lexeme, expr.name.range());
+        self.emit_op_u16(OpCode::SetProperty, constant, expr.name.range());
+    }
+
+    fn visit_super(self, expr: &Super, _: ()) {
+        let span = expr.keyword.range();
+        self.emit_variable("this", span.clone(), false);
+        self.emit_variable("super", span.clone(), false);
+        let constant = self.identifier_constant(&expr.method.lexeme, expr.method.range());
+        self.emit_op_u16(OpCode::GetSuper, constant, expr.method.range());
+    }
+
+    fn visit_this(self, expr: &This, _: ()) {
+        self.emit_variable("this", expr.keyword.range(), false);
+    }
+
+    fn visit_unary(self, expr: &Unary, _: ()) {
+        self.compile_expr(&expr.right);
+        let span = expr.operator.range();
+        match expr.operator.kind {
+            TokenKind::Bang => self.emit_op(OpCode::Not, span.clone()),
+            TokenKind::Minus => self.emit_op(OpCode::Negate, span.clone()),
+            TokenKind::InterpolationStart => self.emit_op(OpCode::Stringify, span.clone()),
+            _ => unreachable!("invalid unary operator `{}`", expr.operator),
+        }
+    }
+
+    fn visit_variable(self, expr: &Variable, _: ()) {
+        self.emit_variable(&expr.name.lexeme, expr.name.range(), false);
+    }
+}
+
+impl StmtVisitor<(), ()> for &mut Compiler {
+    fn visit_block(self, stmt: &Block, _: ()) {
+        self.begin_scope();
+        self.compile_stmts(&stmt.stmts);
+        self.end_scope();
+    }
+
+    fn visit_class(self, stmt: &Class, _: ()) {
+        let name = &stmt.name;
+        let constant = self.identifier_constant(&name.lexeme, name.range());
+        self.emit_op_u16(OpCode::Class, constant, name.range());
+        self.define_variable(&name.lexeme, name.range());
+
+        // The superclass is kept as a local `super` in a scope enclosing the methods.
+        if let Some(superclass) = &stmt.superclass {
+            let span = superclass.name.range();
+            self.begin_scope();
+            self.emit_variable(&superclass.name.lexeme, span.clone(), false);
+            self.add_local("super", span.clone());
+            self.emit_variable(&name.lexeme, name.range(), false);
+            self.emit_op(OpCode::Inherit, span.clone());
+        }
+
+        // The class is kept on the stack while its methods are added to it.
+        self.emit_variable(&name.lexeme, name.range(), false);
+        for method in &stmt.methods {
+            let kind = if method.name.lexeme == "init" {
+                FunctionKind::Initializer
+            } else {
+                FunctionKind::Method
+            };
+            self.compile_function(method, kind);
+            let constant = self.identifier_constant(&method.name.lexeme, method.name.range());
+            self.emit_op_u16(OpCode::Method, constant, method.name.range());
+        }
+        self.emit_op(OpCode::Pop, name.range());
+
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+    }
+
+    fn visit_expression(self, stmt: &Expression, _: ()) {
+        self.compile_expr(&stmt.expr);
+        self.emit_op(OpCode::Pop, self.last_span());
+    }
+
+    fn visit_function(self, stmt: &ast::Function, _: ()) {
+        // A local function is declared before its body is compiled, so that it can refer to itself recursively.
+        let name = &stmt.name;
+        if self.current_depth() > 0 {
+            self.add_local(&name.lexeme, name.range());
+            self.compile_function(stmt, FunctionKind::Function);
+        } else {
+            self.compile_function(stmt, FunctionKind::Function);
+            self.define_variable(&name.lexeme, name.range());
+        }
+    }
+
+    fn visit_if(self, stmt: &If, _: ()) {
+        self.compile_expr(&stmt.condition);
+        let span = self.last_span();
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse, span.clone());
+        self.emit_op(OpCode::Pop, span.clone());
+        stmt.then_branch.accept(&mut *self, ());
+
+        let else_jump = self.emit_jump(OpCode::Jump, self.last_span());
+        self.patch_jump(then_jump);
+        self.emit_op(OpCode::Pop, span.clone());
+        if let Some(else_branch) = &stmt.else_branch {
+            else_branch.accept(&mut *self, ());
+        }
+        self.patch_jump(else_jump);
+    }
+
+    fn visit_print(self, stmt: &Print, _: ()) {
+        self.compile_expr(&stmt.expr);
+        self.emit_op(OpCode::Print, self.last_span());
+    }
+
+    fn visit_return(self, stmt: &Return, _: ()) {
+        let span = stmt.keyword.range();
+        match &stmt.value {
+            Some(value) => {
+                self.compile_expr(value);
+                self.emit_op(OpCode::Return, span.clone());
+            },
+            None => self.emit_return(span.clone()),
+        }
+    }
+
+    fn visit_var(self, stmt: &Var, _: ()) {
+        match &stmt.initializer {
+            Some(initializer) => self.compile_expr(initializer),
+            None => self.emit_op(OpCode::Nil, stmt.name.range()),
+        }
+        self.define_variable(&stmt.name.lexeme, stmt.name.range());
+    }
+
+    fn visit_while(self, stmt: &While, _: ()) {
+        let start = self.chunk().code().len();
+        self.compile_expr(&stmt.condition);
+        let span = self.last_span();
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse, span.clone());
+        self.emit_op(OpCode::Pop, span.clone());
+        stmt.body.accept(&mut *self, ());
+        self.emit_loop(start, span.clone());
+        self.patch_jump(exit_jump);
+        self.emit_op(OpCode::Pop, span.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+
+    fn compile(source: &str) -> Result<Rc<Function>, Vec<Error>> {
+        let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+        let stmts = Parser::new(&tokens).parse().unwrap();
+        Resolver::new().resolve(&stmts).unwrap();
+        Compiler::new().compile(&stmts)
+    }
+
+    #[test]
+    fn compiler_compile() {
+        use OpCode::{
+            Closure, DefineGlobal, GetGlobal, GetLocal, GetUpvalue, JumpIfFalse, Negate, Nil, Pop, Print, Return,
+        };
+        let function = compile("var a = 1; { var b = -a; print b and 2; }").unwrap();
+        let chunk = &function.chunk;
+        assert_eq!(chunk.code(), [
+            OpCode::Constant as u8, 0, 0,
+            DefineGlobal as u8, 0, 1,
+            GetGlobal as u8, 0, 2,
+            Negate as u8,
+            GetLocal as u8, 1,
+            JumpIfFalse as u8, 0, 4,
+            Pop as u8,
+            OpCode::Constant as u8, 0, 3,
+            Print as u8,
+            Pop as u8,
+            Nil as u8,
+            Return as u8,
+        ]);
+        assert_eq!(chunk.span_at(9), 21..22);
+        assert_eq!(chunk.span_at(12), 33..36);
+
+        let function = compile("fun f(a) { fun g() { return a; } return g; }").unwrap();
+        let Constant::Function(f) = &function.chunk.constants()[0] else { panic!() };
+        let Constant::Function(g) = &f.chunk.constants()[0] else { panic!() };
+        assert_eq!((f.arity, f.upvalue_count), (1, 0));
+        assert_eq!((g.arity, g.upvalue_count), (0, 1));
+        assert_eq!(f.chunk.code()[..5], [Closure as u8, 0, 0, 1, 1]);
+        assert_eq!(g.chunk.code()[..3], [GetUpvalue as u8, 0, Return as u8]);
+    }
+
+    #[test]
+    fn compiler_compile_error() {
+        let vars = (0..MAX_LOCALS).map(|i| format!("var a{i};")).collect::<String>();
+        let source = format!("{{ {vars} }}");
+        assert_eq!(compile(&source).err().unwrap(), vec![Error::TooManyLocals {
+            snippet: Snippet::new(source.rfind("a255").unwrap()..source.rfind(";").unwrap()),
+        }]);
+
+        let source = format!("while (true) {{ {} }}", "1;".repeat(MAX_JUMP / 4));
+        assert_eq!(compile(&source).err().unwrap(), vec![
+            Error::JumpTooLarge {
+                snippet: Snippet::new(7..11),
+            },
+            Error::JumpTooLarge {
+                snippet: Snippet::new(7..11),
+            },
+        ]);
+    }
+}
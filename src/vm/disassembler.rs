@@ -0,0 +1,171 @@
+use std::fmt::{self, Display, Formatter};
+use crate::src::{Index, SourceMap};
+use crate::vm::chunk::{Chunk, Constant, OpCode, Operand};
+use crate::vm::value::Function;
+
+/// Displays the bytecode of a function, followed by the bytecode of every function declared in it,
+/// with each instruction annotated with its offset in the chunk and the source line it's compiled from.
+pub struct Disassembler<'a> {
+    sources: &'a SourceMap,
+    function: &'a Function,
+}
+
+impl<'a> Disassembler<'a> {
+    /// Creates a disassembler of `function`, which must be compiled from a source in `sources`.
+    pub fn new(sources: &'a SourceMap, function: &'a Function) -> Self {
+        Disassembler { sources, function }
+    }
+
+    fn fmt_function(&self, function: &Function, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "== {function} ==")?;
+        let chunk = &function.chunk;
+        let mut index = 0;
+        let mut previous_line = None;
+        while index < chunk.code().len() {
+            let line = self.line_at(chunk, index);
+            if previous_line == Some(line) {
+                write!(f, "{index:04}    | ")?;
+            } else {
+                write!(f, "{index:04} {line:4} ")?;
+            }
+            previous_line = Some(line);
+            index = self.fmt_instruction(chunk, index, f)?;
+        }
+
+        for constant in chunk.constants() {
+            if let Constant::Function(function) = constant {
+                writeln!(f)?;
+                self.fmt_function(function, f)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Displays the instruction at `index`, and returns the index of the next one.
+    fn fmt_instruction(&self, chunk: &Chunk, index: usize, f: &mut Formatter<'_>) -> Result<usize, fmt::Error> {
+        let code = chunk.code();
+        let op = match OpCode::try_from(code[index]) {
+            Ok(op) => op,
+            Err(byte) => {
+                writeln!(f, "<invalid opcode {byte}>")?;
+                return Ok(index + 1);
+            },
+        };
+
+        let name = format!("{op:?}");
+        match op.operand() {
+            Operand::Constant => {
+                let constant = read_u16(code, index + 1);
+                writeln!(f, "{name:<16} {constant:5} '{}'", chunk.constants()[constant as usize])?;
+                Ok(index + 3)
+            },
+            Operand::Byte => {
+                writeln!(f, "{name:<16} {:5}", code[index + 1])?;
+                Ok(index + 2)
+            },
+            Operand::Jump => {
+                let distance = read_u16(code, index + 1) as usize;
+                let target = if op == OpCode::Loop { index + 3 - distance } else { index + 3 + distance };
+                writeln!(f, "{name:<16} {index:5} -> {target}")?;
+                Ok(index + 3)
+            },
+            Operand::Closure => {
+                let constant = read_u16(code, index + 1);
+                let function = &chunk.constants()[constant as usize];
+                writeln!(f, "{name:<16} {constant:5} '{function}'")?;
+
+                let upvalue_count = match function {
+                    Constant::Function(function) => function.upvalue_count,
+                    _ => 0,
+                };
+                let mut next = index + 3;
+                for _ in 0..upvalue_count {
+                    let kind = if code[next] == 1 { "local" } else { "upvalue" };
+                    writeln!(f, "{next:04}    | {:<16} {:5} {kind}", "", code[next + 1])?;
+                    next += 2;
+                }
+                Ok(next)
+            },
+            Operand::None => {
+                writeln!(f, "{name}")?;
+                Ok(index + 1)
+            },
+        }
+    }
+
+    /// Returns the 1-based source line which the byte at `index` is compiled from, or 0 if its source isn't found.
+    fn line_at(&self, chunk: &Chunk, index: usize) -> Index {
+        let offset = chunk.span_at(index).start;
+        self.sources.lookup(offset).map_or(0, |id| {
+            let file = self.sources.file(id);
+            file.line_of(offset - file.start()) + 1
+        })
+    }
+}
+
+impl Display for Disassembler<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.fmt_function(self.function, f)
+    }
+}
+
+#[inline(always)]
+fn read_u16(code: &[u8], index: usize) -> u16 {
+    u16::from_be_bytes([code[index], code[index + 1]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+    use crate::vm::compiler::Compiler;
+
+    #[test]
+    fn disassembler_fmt() {
+        let source = "var a = 1;\nfun f(b) {\n  if (b) return a;\n  return fun_g;\n}\n{ var c; fun h() { c = 2; } }\n";
+        let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+        let stmts = Parser::new(&tokens).parse().unwrap();
+        Resolver::new().resolve(&stmts).unwrap();
+        let function = Compiler::new().compile(&stmts).unwrap();
+
+        let mut sources = SourceMap::new();
+        sources.add("a.lox", source.as_bytes());
+        assert_eq!(Disassembler::new(&sources, &function).to_string(), [
+            "== <script> ==",
+            "0000    1 Constant             0 '1'",
+            "0003    | DefineGlobal         1 'a'",
+            "0006    2 Closure              2 '<fn f>'",
+            "0009    | DefineGlobal         3 'f'",
+            "0012    6 Nil",
+            "0013    | Closure              4 '<fn h>'",
+            "0016    |                      1 local",
+            "0018    | Pop",
+            "0019    | CloseUpvalue",
+            "0020    | Nil",
+            "0021    | Return",
+            "",
+            "== <fn f> ==",
+            "0000    3 GetLocal             1",
+            "0002    | JumpIfFalse          2 -> 13",
+            "0005    | Pop",
+            "0006    | GetGlobal            0 'a'",
+            "0009    | Return",
+            "0010    | Jump                10 -> 14",
+            "0013    | Pop",
+            "0014    4 GetGlobal            1 'fun_g'",
+            "0017    | Return",
+            "0018    | Nil",
+            "0019    | Return",
+            "",
+            "== <fn h> ==",
+            "0000    6 Constant             0 '2'",
+            "0003    | SetUpvalue           0",
+            "0005    | Pop",
+            "0006    | Nil",
+            "0007    | Return",
+            "",
+        ].join("\n"));
+    }
+}
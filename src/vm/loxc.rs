@@ -0,0 +1,608 @@
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+use thiserror::Error;
+use crate::consts::limit::MAX_ARGUMENTS;
+use crate::src::Index;
+use crate::vm::chunk::{Chunk, Constant, OpCode, Operand};
+use crate::vm::value::Function;
+
+/// The first bytes of every compiled Lox file.
+pub const MAGIC: &[u8; 4] = b"LOXC";
+
+/// The version of the format, which must be bumped on every change to the format or to the bytecode.
+pub const FORMAT_VERSION: u16 = 3;
+
+const HEADER_LEN: usize = MAGIC.len() + 2 + 8 + 8;
+
+const TAG_NUMBER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_FUNCTION: u8 = 2;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("not a compiled Lox file")]
+    NotLoxc,
+
+    #[error("compiled Lox file of format version {found} can't be run by this version, \
+             which supports format version {FORMAT_VERSION}")]
+    VersionMismatch {
+        found: u16,
+    },
+
+    #[error("corrupt compiled Lox file: {reason}")]
+    Corrupt {
+        reason: String,
+    },
+}
+
+impl Error {
+    fn corrupt(reason: impl Into<String>) -> Self {
+        Error::Corrupt {
+            reason: reason.into(),
+        }
+    }
+}
+
+/// A compiled Lox file, which holds the top-level script function compiled from a source,
+/// together with the hash of the source.
+///
+/// The file consists of a header and a body, where every integer is encoded in little-endian:
+/// - header: `MAGIC`, `FORMAT_VERSION: u16`, `source_hash: u64`, and the checksum of the body `u64`.
+/// - body: the script function, where a function is encoded as its name, `arity: u32`,
+///   `upvalue_count: u32`, code, line table, and constants, each of which is prefixed by its length `u32`.
+///   Each entry of the line table is the range of the source `start: u32` and `end: u32`,
+///   and the number of bytes of code compiled from it `u32`.
+pub struct Loxc {
+    pub source_hash: u64,
+    pub function: Rc<Function>,
+}
+
+impl Loxc {
+    pub fn new(source: &[u8], function: Rc<Function>) -> Self {
+        Loxc {
+            source_hash: hash(source),
+            function,
+        }
+    }
+
+    /// Returns whether this is compiled from `source`.
+    pub fn is_compiled_from(&self, source: &[u8]) -> bool {
+        self.source_hash == hash(source)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        write_function(&mut body, &self.function);
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + body.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.source_hash.to_le_bytes());
+        bytes.extend_from_slice(&hash(&body).to_le_bytes());
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    /// Decodes a compiled Lox file, and verifies its bytecode as `verify` does.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(Error::NotLoxc);
+        }
+
+        let mut reader = Reader::new(&bytes[MAGIC.len()..]);
+        let version = reader.read_u16()?;
+        if version != FORMAT_VERSION {
+            return Err(Error::VersionMismatch { found: version });
+        }
+
+        let source_hash = reader.read_u64()?;
+        let checksum = reader.read_u64()?;
+        if hash(reader.rest()) != checksum {
+            return Err(Error::corrupt("checksum mismatch"));
+        }
+
+        let function = reader.read_function()?;
+        if !reader.rest().is_empty() {
+            return Err(Error::corrupt("trailing bytes after the script"));
+        }
+        // The script is run with nothing but itself on the stack, and captures no variables.
+        if function.arity != 0 || function.upvalue_count != 0 {
+            return Err(Error::corrupt("script with parameters or captured variables"));
+        }
+        verify(&function)?;
+
+        Ok(Loxc {
+            source_hash,
+            function: Rc::new(function),
+        })
+    }
+}
+
+/// Returns the 64-bit FNV-1a hash of `bytes`, which is stable across platforms and Rust versions.
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+fn write_function(bytes: &mut Vec<u8>, function: &Function) {
+    write_str(bytes, &function.name);
+    write_u32(bytes, function.arity);
+    write_u32(bytes, function.upvalue_count);
+
+    let chunk = &function.chunk;
+    write_u32(bytes, chunk.code().len());
+    bytes.extend_from_slice(chunk.code());
+
+    write_u32(bytes, chunk.spans().len());
+    for (span, count) in chunk.spans() {
+        write_u32(bytes, span.start);
+        write_u32(bytes, span.end);
+        write_u32(bytes, *count);
+    }
+
+    write_u32(bytes, chunk.constants().len());
+    for constant in chunk.constants() {
+        match constant {
+            Constant::Number(n) => {
+                bytes.push(TAG_NUMBER);
+                bytes.extend_from_slice(&n.to_le_bytes());
+            },
+            Constant::String(s) => {
+                bytes.push(TAG_STRING);
+                write_str(bytes, s);
+            },
+            Constant::Function(function) => {
+                bytes.push(TAG_FUNCTION);
+                write_function(bytes, function);
+            },
+        }
+    }
+}
+
+#[inline(always)]
+fn write_u32(bytes: &mut Vec<u8>, n: usize) {
+    let n = u32::try_from(n).expect("compiled code must be smaller than 4 GiB");
+    bytes.extend_from_slice(&n.to_le_bytes());
+}
+
+#[inline(always)]
+fn write_str(bytes: &mut Vec<u8>, s: &str) {
+    write_u32(bytes, s.len());
+    bytes.extend_from_slice(s.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    next: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, next: 0 }
+    }
+
+    fn read_function(&mut self) -> Result<Function, Error> {
+        let name = self.read_str()?;
+        let arity = self.read_u32()?;
+        let upvalue_count = self.read_u32()?;
+
+        let len = self.read_u32()?;
+        let code = self.read_bytes(len)?.to_vec();
+
+        let len = self.read_u32()?;
+        let mut spans = Vec::new();
+        for _ in 0..len {
+            let start: Index = self.read_u32()?;
+            let end: Index = self.read_u32()?;
+            let count = self.read_u32()?;
+            spans.push((start..end, count));
+        }
+
+        let len = self.read_u32()?;
+        let mut constants = Vec::new();
+        for _ in 0..len {
+            let constant = match self.read_u8()? {
+                TAG_NUMBER => Constant::Number(f64::from_le_bytes(self.read_array()?)),
+                TAG_STRING => Constant::String(self.read_str()?.into()),
+                TAG_FUNCTION => Constant::Function(Rc::new(self.read_function()?)),
+                tag => return Err(Error::corrupt(format!("invalid constant tag {tag}"))),
+            };
+            constants.push(constant);
+        }
+
+        Ok(Function {
+            name: name.into(),
+            arity,
+            upvalue_count,
+            chunk: Chunk::from_parts(code, constants, spans),
+        })
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let bytes = self.bytes
+            .get(self.next..)
+            .and_then(|rest| rest.get(..len))
+            .ok_or_else(|| Error::corrupt("unexpected end of file"))?;
+        self.next += len;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        Ok(self.read_bytes(N)?.try_into().expect("slice must have the requested length"))
+    }
+
+    #[inline(always)]
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    #[inline(always)]
+    fn read_u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    #[inline(always)]
+    fn read_u32(&mut self) -> Result<usize, Error> {
+        Ok(u32::from_le_bytes(self.read_array()?) as usize)
+    }
+
+    #[inline(always)]
+    fn read_u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_str(&mut self) -> Result<&'a str, Error> {
+        let len = self.read_u32()?;
+        str::from_utf8(self.read_bytes(len)?).map_err(|_| Error::corrupt("invalid utf-8 string"))
+    }
+
+    #[inline(always)]
+    fn rest(&self) -> &'a [u8] {
+        &self.bytes[self.next..]
+    }
+}
+
+/// An instruction decoded by `verify`.
+struct Instruction {
+    op: OpCode,
+    /// The index of the instruction following it in the code.
+    next: usize,
+    /// The 1-byte operand, or the index a jump lands on.
+    operand: usize,
+    /// The local slots captured by a closure.
+    captures: Vec<usize>,
+}
+
+/// Verifies that every instruction of `function`, and of the functions declared in it, is well-formed:
+/// its operands are within the code, and refer to existing constants of the right kinds, instructions,
+/// local slots, and variables captured by the function. The stack is traced through every path of the code,
+/// so that each instruction finds the values it takes on the stack, and no captured variable is discarded
+/// without being closed.
+fn verify(function: &Function) -> Result<(), Error> {
+    let corrupt = |reason: String| Error::corrupt(format!("{reason} in `{function}`"));
+    let chunk = &function.chunk;
+    let code = chunk.code();
+    let constants = chunk.constants();
+
+    if chunk.spans().iter().map(|(_, count)| count).sum::<usize>() != code.len() {
+        return Err(corrupt("line table not matching the code".to_string()));
+    }
+    if chunk.spans().iter().any(|(span, _)| span.start > span.end) {
+        return Err(corrupt("invalid range in the line table".to_string()));
+    }
+    if function.arity > MAX_ARGUMENTS {
+        return Err(corrupt(format!("too many parameters {}", function.arity)));
+    }
+
+    let mut instructions = BTreeMap::new();
+    let mut index = 0;
+    while index < code.len() {
+        let op = OpCode::try_from(code[index]).map_err(|byte| corrupt(format!("invalid opcode {byte} at {index}")))?;
+        let operand = |i: usize| {
+            code.get(index + i).copied().ok_or_else(|| corrupt(format!("truncated `{op:?}` at {index}")))
+        };
+        let operand_u16 = || Ok::<_, Error>(u16::from_be_bytes([operand(1)?, operand(2)?]) as usize);
+
+        let (len, operand, captures) = match op.operand() {
+            Operand::None => (1, 0, Vec::new()),
+            Operand::Byte => {
+                let byte = operand(1)? as usize;
+                if matches!(op, OpCode::GetUpvalue | OpCode::SetUpvalue) && byte >= function.upvalue_count {
+                    return Err(corrupt(format!("invalid upvalue {byte} at {index}")));
+                }
+                (2, byte, Vec::new())
+            },
+            Operand::Constant => {
+                let constant = constants.get(operand_u16()?);
+                let valid = match op {
+                    OpCode::Constant => matches!(constant, Some(Constant::Number(_) | Constant::String(_))),
+                    _ => matches!(constant, Some(Constant::String(_))),
+                };
+                if !valid {
+                    return Err(corrupt(format!("invalid constant of `{op:?}` at {index}")));
+                }
+                (3, 0, Vec::new())
+            },
+            Operand::Jump => {
+                let distance = operand_u16()?;
+                let target = if op == OpCode::Loop {
+                    (index + 3).checked_sub(distance)
+                } else {
+                    Some(index + 3 + distance)
+                };
+                match target {
+                    Some(target) if target < code.len() => (3, target, Vec::new()),
+                    _ => return Err(corrupt(format!("invalid jump target of `{op:?}` at {index}"))),
+                }
+            },
+            Operand::Closure => {
+                let Some(Constant::Function(closure)) = constants.get(operand_u16()?) else {
+                    return Err(corrupt(format!("invalid constant of `{op:?}` at {index}")));
+                };
+                let mut captures = Vec::new();
+                for i in 0..closure.upvalue_count {
+                    let is_local = operand(3 + i * 2)?;
+                    let upvalue = operand(4 + i * 2)? as usize;
+                    match is_local {
+                        1 => captures.push(upvalue),
+                        0 if upvalue < function.upvalue_count => (),
+                        _ => return Err(corrupt(format!("invalid captured variable of `{op:?}` at {index}"))),
+                    }
+                }
+                (3 + closure.upvalue_count * 2, 0, captures)
+            },
+        };
+        instructions.insert(index, Instruction { op, next: index + len, operand, captures });
+        index += len;
+    }
+
+    if instructions.last_key_value().is_none_or(|(_, last)| last.op != OpCode::Return) {
+        return Err(corrupt("code not ending with a return".to_string()));
+    }
+    // A jump must land on the start of an instruction, not inside its operands.
+    for (index, instruction) in &instructions {
+        if instruction.op.operand() == Operand::Jump && !instructions.contains_key(&instruction.operand) {
+            return Err(corrupt(format!("invalid jump target of `{:?}` at {index}", instruction.op)));
+        }
+    }
+
+    // The stack of the frame before each instruction reached, as whether each slot is captured by a closure.
+    // Its depth must be the same on every path reaching the instruction.
+    let mut stacks = HashMap::<usize, Vec<bool>>::new();
+    // The callee and its arguments are on the stack when the function starts.
+    let mut pending = vec![(0, vec![false; 1 + function.arity])];
+    while let Some((index, mut stack)) = pending.pop() {
+        match stacks.get_mut(&index) {
+            Some(known) if known.len() != stack.len() => {
+                return Err(corrupt(format!("inconsistent stack depth at {index}")));
+            },
+            Some(known) if known.iter().zip(&stack).all(|(&k, &s)| k || !s) => continue,
+            Some(known) => {
+                known.iter_mut().zip(&stack).for_each(|(k, &s)| *k |= s);
+                stack = known.clone();
+            },
+            None => {
+                stacks.insert(index, stack.clone());
+            },
+        }
+
+        let Instruction { op, next, operand, ref captures } = instructions[&index];
+        let (pops, pushes) = stack_effect(op, operand);
+        if stack.len() < pops {
+            return Err(corrupt(format!("stack underflow of `{op:?}` at {index}")));
+        }
+        match op {
+            OpCode::GetLocal | OpCode::SetLocal if operand >= stack.len() => {
+                return Err(corrupt(format!("invalid local {operand} at {index}")));
+            },
+            OpCode::CloseUpvalue => *stack.last_mut().expect("stack must have been checked") = false,
+            _ => (),
+        }
+
+        let depth = stack.len() - pops + pushes;
+        if stack.get(depth..).is_some_and(|discarded| discarded.contains(&true)) {
+            return Err(corrupt(format!("captured variable discarded by `{op:?}` at {index}")));
+        }
+        stack.resize(depth, false);
+        // A closure may capture the slot it's pushed into, which is a local function referring to itself.
+        for &slot in captures {
+            *stack.get_mut(slot).ok_or_else(|| corrupt(format!("invalid local {slot} at {index}")))? = true;
+        }
+
+        match op {
+            OpCode::Return => (),
+            OpCode::Jump | OpCode::Loop => pending.push((operand, stack)),
+            OpCode::JumpIfFalse => {
+                pending.push((operand, stack.clone()));
+                pending.push((next, stack));
+            },
+            _ => pending.push((next, stack)),
+        }
+    }
+
+    for constant in constants {
+        if let Constant::Function(declared) = constant {
+            verify(declared)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the number of values the instruction `op` takes from the stack, and the number of values it leaves,
+/// where `operand` is its 1-byte operand, if any.
+fn stack_effect(op: OpCode, operand: usize) -> (usize, usize) {
+    use OpCode::*;
+    match op {
+        Constant | Nil | True | False | GetLocal | GetGlobal | GetUpvalue | Closure | Class => (0, 1),
+        Jump | Loop => (0, 0),
+        Pop | DefineGlobal | Print | CloseUpvalue | Return => (1, 0),
+        SetLocal | SetGlobal | SetUpvalue | GetProperty | Not | Negate | Stringify | JumpIfFalse => (1, 1),
+        SetProperty | GetSuper | Equal | Greater | GreaterEqual | Less | LessEqual | Add | Subtract | Multiply
+        | Divide | Inherit | Method => (2, 1),
+        Call => (operand + 1, 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+    use super::*;
+    use crate::interpreter::RuntimeError;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+    use crate::src::SourceMap;
+    use crate::vm::Vm;
+    use crate::vm::compiler::Compiler;
+    use crate::vm::disassembler::Disassembler;
+
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const SOURCE: &str = r#"
+        class A {
+            init(n) { this.n = n; }
+            add(m) { fun f() { return this.n + m; } return f; }
+        }
+        var x = 0.5;
+        for (var i = 0; i < 2; i = i + 1) x = x + i;
+        print A(x).add(1)();
+        print "done";
+    "#;
+
+    fn compile(source: &str) -> Rc<Function> {
+        let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+        let stmts = Parser::new(&tokens).parse().unwrap();
+        Resolver::new().resolve(&stmts).unwrap();
+        Compiler::new().compile(&stmts).unwrap()
+    }
+
+    /// Replaces the body of `bytes` after the header, and updates its checksum.
+    fn with_body(bytes: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut bytes = bytes[..HEADER_LEN].to_vec();
+        bytes[HEADER_LEN - 8..].copy_from_slice(&hash(body).to_le_bytes());
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    #[test]
+    fn loxc_to_bytes_from_bytes() {
+        let function = compile(SOURCE);
+        let bytes = Loxc::new(SOURCE.as_bytes(), function.clone()).to_bytes();
+        assert!(bytes.starts_with(MAGIC));
+
+        let loxc = Loxc::from_bytes(&bytes).unwrap();
+        assert!(loxc.is_compiled_from(SOURCE.as_bytes()));
+        assert!(!loxc.is_compiled_from(b"print 1;"));
+        let mut sources = SourceMap::new();
+        sources.add("a.lox", SOURCE.as_bytes());
+        assert_eq!(
+            Disassembler::new(&sources, &loxc.function).to_string(),
+            Disassembler::new(&sources, &function).to_string(),
+        );
+
+        let output = Output::default();
+        Vm::with_output(output.clone()).interpret(loxc.function).unwrap();
+        assert_eq!(String::from_utf8(output.0.take()).unwrap(), "2.5\ndone\n");
+    }
+
+    #[test]
+    fn loxc_from_bytes_error() {
+        let bytes = Loxc::new(SOURCE.as_bytes(), compile(SOURCE)).to_bytes();
+        let body = &bytes[HEADER_LEN..];
+        let error = |bytes: &[u8]| Loxc::from_bytes(bytes).err().unwrap();
+
+        assert_eq!(error(b"print 1;"), Error::NotLoxc);
+
+        let mut newer = bytes.clone();
+        newer[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert_eq!(error(&newer), Error::VersionMismatch { found: FORMAT_VERSION + 1 });
+
+        let mut flipped = bytes.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        assert_eq!(error(&flipped), Error::corrupt("checksum mismatch"));
+        assert_eq!(error(&bytes[..HEADER_LEN - 1]), Error::corrupt("unexpected end of file"));
+        assert_eq!(error(&with_body(&bytes, &body[..body.len() - 1])), Error::corrupt("unexpected end of file"));
+        assert_eq!(error(&with_body(&bytes, &[body, &[0]].concat())), Error::corrupt("trailing bytes after the script"));
+
+        let function = Function {
+            name: "".into(),
+            arity: 0,
+            upvalue_count: 0,
+            chunk: Chunk::from_parts(vec![OpCode::GetGlobal as u8, 0, 1, OpCode::Return as u8], vec![], vec![(0..0, 4)]),
+        };
+        let invalid = Loxc::new(b"", Rc::new(function)).to_bytes();
+        assert_eq!(error(&invalid), Error::corrupt("invalid constant of `GetGlobal` at 0 in `<script>`"));
+
+        let function = Function {
+            name: "".into(),
+            arity: 0,
+            upvalue_count: 0,
+            chunk: Chunk::from_parts(vec![OpCode::Jump as u8, 0, 1, OpCode::Return as u8], vec![], vec![(0..0, 4)]),
+        };
+        let invalid = Loxc::new(b"", Rc::new(function)).to_bytes();
+        assert_eq!(error(&invalid), Error::corrupt("invalid jump target of `Jump` at 0 in `<script>`"));
+    }
+
+    #[test]
+    fn loxc_from_bytes_stack_error() {
+        use OpCode::*;
+        let error = |code: Vec<u8>, constants| {
+            let len = code.len();
+            let function = Function {
+                name: "".into(),
+                arity: 0,
+                upvalue_count: 0,
+                chunk: Chunk::from_parts(code, constants, vec![(0..0, len)]),
+            };
+            Loxc::from_bytes(&Loxc::new(b"", Rc::new(function)).to_bytes()).err().unwrap()
+        };
+
+        let cases = [
+            (vec![GetLocal as u8, 50, Print as u8, Nil as u8, Return as u8], "invalid local 50 at 0"),
+            (vec![Pop as u8, Pop as u8, Nil as u8, Return as u8], "stack underflow of `Pop` at 1"),
+            (vec![Nil as u8, Call as u8, 2, Return as u8], "stack underflow of `Call` at 1"),
+            (vec![Jump as u8, 0, 1, GetLocal as u8, 0, Return as u8], "invalid jump target of `Jump` at 0"),
+            (vec![True as u8, JumpIfFalse as u8, 0, 1, Nil as u8, Return as u8], "inconsistent stack depth at 5"),
+        ];
+        for (code, reason) in cases {
+            assert_eq!(error(code, vec![]), Error::corrupt(format!("{reason} in `<script>`")));
+        }
+
+        // The local captured in `{ var a; fun f() { return a; } }` must be closed, not popped.
+        let f = Function {
+            name: "f".into(),
+            arity: 0,
+            upvalue_count: 1,
+            chunk: Chunk::from_parts(vec![GetUpvalue as u8, 0, Return as u8], vec![], vec![(0..0, 3)]),
+        };
+        let code = vec![Nil as u8, Closure as u8, 0, 0, 1, 1, Pop as u8, Pop as u8, Nil as u8, Return as u8];
+        assert_eq!(
+            error(code, vec![crate::vm::chunk::Constant::Function(Rc::new(f))]),
+            Error::corrupt("captured variable discarded by `Pop` at 7 in `<script>`"),
+        );
+
+        // The types of values aren't verified, but a value of the wrong type fails the VM.
+        let code = vec![Constant as u8, 0, 0, GetLocal as u8, 0, GetLocal as u8, 1, GetSuper as u8, 0, 1, Return as u8];
+        let constants = vec![crate::vm::chunk::Constant::Number(1.0), crate::vm::chunk::Constant::String("m".into())];
+        let function = Function {
+            name: "".into(),
+            arity: 0,
+            upvalue_count: 0,
+            chunk: Chunk::from_parts(code, constants, vec![(0..0, 11)]),
+        };
+        let loxc = Loxc::from_bytes(&Loxc::new(b"", Rc::new(function)).to_bytes()).unwrap();
+        let error = Vm::with_output(Output::default()).interpret(loxc.function).err().unwrap();
+        assert!(matches!(error, RuntimeError::CorruptBytecode { .. }), "{error}");
+    }
+}
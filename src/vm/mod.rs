@@ -0,0 +1,779 @@
+pub mod chunk;
+pub mod compiler;
+pub mod disassembler;
+pub mod loxc;
+pub mod value;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::rc::Rc;
+use crate::consts::limit::MAX_CALL_DEPTH;
+use crate::gc::{GcConfig, Heap};
+use crate::interpreter::RuntimeError;
+use crate::src::Snippet;
+use crate::types::Number;
+use crate::vm::chunk::{Constant, OpCode};
+use crate::vm::value::{BoundMethod, Class, Closure, Function, Instance, Upvalue, Value};
+
+/// The activation of a closure being called.
+struct CallFrame {
+    closure: Rc<Closure>,
+    /// The index of the next byte to execute in the chunk of the closure.
+    ip: usize,
+    /// The index of the first stack slot of the frame, which holds the callee.
+    base: usize,
+}
+
+/// A stack-based virtual machine, which runs the bytecode compiled by `Compiler`
+/// with the same semantics as `Interpreter`.
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<Rc<str>, Value>,
+    /// The captured variables which still live on the stack.
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    heap: Heap,
+    out: Box<dyn Write>,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+
+    /// Creates a VM whose `print` statements write to `out` instead of the standard output.
+    pub fn with_output(out: impl Write + 'static) -> Self {
+        Self::with_gc(out, GcConfig::default())
+    }
+
+    /// Creates a VM whose `print` statements write to `out`, and whose heap is collected as configured.
+    pub fn with_gc(out: impl Write + 'static, gc: GcConfig) -> Self {
+        Vm {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: HashMap::new(),
+            open_upvalues: Vec::new(),
+            heap: Heap::new(gc),
+            out: Box::new(out),
+        }
+    }
+
+    /// Runs the top-level script `function`, keeping the global variables it defines for later runs.
+    pub fn interpret(&mut self, function: Rc<Function>) -> Result<(), RuntimeError> {
+        let closure = self.heap.alloc(Closure::new(function, Vec::new()));
+        self.stack.push(Value::Closure(closure.clone()));
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            base: 0,
+        });
+
+        let result = self.run();
+        if result.is_err() {
+            // Variables captured by closures which outlive this run must not refer to a stale stack.
+            self.close_upvalues(0);
+            self.stack.clear();
+            self.frames.clear();
+        }
+        result
+    }
+
+    fn run(&mut self) -> Result<(), RuntimeError> {
+        loop {
+            let ip = self.frame().ip;
+            let op = self.read_u8();
+            let op = OpCode::try_from(op).unwrap_or_else(|byte| panic!("invalid opcode {byte} at {ip}"));
+            match op {
+                OpCode::Constant => {
+                    let value = match self.read_constant() {
+                        Constant::Number(n) => Value::Number(n),
+                        Constant::String(s) => Value::String(s),
+                        Constant::Function(f) => unreachable!("function constant `{f}` must be closed over"),
+                    };
+                    self.push(value);
+                },
+                OpCode::Nil => self.push(Value::Nil),
+                OpCode::True => self.push(Value::Bool(true)),
+                OpCode::False => self.push(Value::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                },
+                OpCode::GetLocal => {
+                    let slot = self.frame().base + self.read_u8() as usize;
+                    self.push(self.stack[slot].clone());
+                },
+                OpCode::SetLocal => {
+                    let slot = self.frame().base + self.read_u8() as usize;
+                    self.stack[slot] = self.peek(0).clone();
+                },
+                OpCode::GetGlobal => {
+                    let name = self.read_name();
+                    match self.globals.get(&name) {
+                        Some(value) => self.push(value.clone()),
+                        None => return Err(self.undefined_variable(ip, &name)),
+                    }
+                },
+                OpCode::DefineGlobal => {
+                    let name = self.read_name();
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                },
+                OpCode::SetGlobal => {
+                    let name = self.read_name();
+                    let value = self.peek(0).clone();
+                    match self.globals.get_mut(&name) {
+                        Some(slot) => *slot = value,
+                        None => return Err(self.undefined_variable(ip, &name)),
+                    }
+                },
+                OpCode::GetUpvalue => {
+                    let index = self.read_u8() as usize;
+                    let value = match &*self.frame().closure.upvalues[index].borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.push(value);
+                },
+                OpCode::SetUpvalue => {
+                    let index = self.read_u8() as usize;
+                    let value = self.peek(0).clone();
+                    let upvalue = self.frame().closure.upvalues[index].clone();
+                    match &mut *upvalue.borrow_mut() {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                },
+                OpCode::GetProperty => {
+                    let name = self.read_name();
+                    let Value::Instance(instance) = self.peek(0).clone() else {
+                        return Err(self.not_instance(ip, self.peek(0)));
+                    };
+
+                    let field = instance.borrow().fields.get(&name).cloned();
+                    let value = match field {
+                        Some(value) => value,
+                        None => {
+                            let class = instance.borrow().class.clone();
+                            self.bind_method(ip, &class, &name, Value::Instance(instance))?
+                        },
+                    };
+                    self.pop();
+                    self.push(value);
+                },
+                OpCode::SetProperty => {
+                    let name = self.read_name();
+                    let Value::Instance(instance) = self.peek(1).clone() else {
+                        return Err(self.not_instance(ip, self.peek(1)));
+                    };
+
+                    let value = self.pop();
+                    instance.borrow_mut().fields.insert(name, value.clone());
+                    self.pop();
+                    self.push(value);
+                },
+                OpCode::GetSuper => {
+                    let name = self.read_name();
+                    let Value::Class(superclass) = self.pop() else {
+                        return Err(self.corrupt(ip, "`super` not bound to a class"));
+                    };
+                    let receiver = self.pop();
+                    let method = self.bind_method(ip, &superclass, &name, receiver)?;
+                    self.push(method);
+                },
+                OpCode::Equal => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.push(Value::Bool(left == right));
+                },
+                OpCode::Greater => self.binary_number_op(ip, ">", |a, b| Value::Bool(a > b))?,
+                OpCode::GreaterEqual => self.binary_number_op(ip, ">=", |a, b| Value::Bool(a >= b))?,
+                OpCode::Less => self.binary_number_op(ip, "<", |a, b| Value::Bool(a < b))?,
+                OpCode::LessEqual => self.binary_number_op(ip, "<=", |a, b| Value::Bool(a <= b))?,
+                OpCode::Add => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let value = match (&left, &right) {
+                        (Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+                        (Value::String(a), Value::String(b)) => Value::String(format!("{a}{b}").into()),
+                        _ => {
+                            return Err(RuntimeError::AddOperands {
+                                snippet: self.snippet_at(ip),
+                                left: left.type_name(),
+                                right: right.type_name(),
+                            });
+                        },
+                    };
+                    self.push(value);
+                },
+                OpCode::Subtract => self.binary_number_op(ip, "-", |a, b| Value::Number(a - b))?,
+                OpCode::Multiply => self.binary_number_op(ip, "*", |a, b| Value::Number(a * b))?,
+                OpCode::Divide => self.binary_number_op(ip, "/", |a, b| Value::Number(a / b))?,
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.push(Value::Bool(!value.is_truthy()));
+                },
+                OpCode::Negate => {
+                    let Value::Number(n) = self.peek(0) else {
+                        return Err(RuntimeError::NumberOperand {
+                            snippet: self.snippet_at(ip),
+                            operator: "-".to_string(),
+                            found: self.peek(0).type_name(),
+                        });
+                    };
+                    let value = Value::Number(-n);
+                    self.pop();
+                    self.push(value);
+                },
+                OpCode::Stringify => {
+                    if !matches!(self.peek(0), Value::String(_)) {
+                        let value = self.pop();
+                        self.push(Value::String(value.to_string().into()));
+                    }
+                },
+                OpCode::Print => {
+                    let value = self.pop();
+                    // A closed output must not abort the program, just like a closed terminal.
+                    let _ = writeln!(self.out, "{value}");
+                },
+                OpCode::Jump => {
+                    let distance = self.read_u16() as usize;
+                    self.frame_mut().ip += distance;
+                },
+                OpCode::JumpIfFalse => {
+                    let distance = self.read_u16() as usize;
+                    if !self.peek(0).is_truthy() {
+                        self.frame_mut().ip += distance;
+                    }
+                },
+                OpCode::Loop => {
+                    let distance = self.read_u16() as usize;
+                    self.frame_mut().ip -= distance;
+                },
+                OpCode::Call => {
+                    let argument_count = self.read_u8() as usize;
+                    self.call_value(ip, argument_count)?;
+                },
+                OpCode::Closure => {
+                    let Constant::Function(function) = self.read_constant() else {
+                        unreachable!("closure must be created from a function constant");
+                    };
+
+                    let mut upvalues = Vec::with_capacity(function.upvalue_count);
+                    for _ in 0..function.upvalue_count {
+                        let is_local = self.read_u8() == 1;
+                        let index = self.read_u8() as usize;
+                        let upvalue = if is_local {
+                            self.capture_upvalue(self.frame().base + index)
+                        } else {
+                            self.frame().closure.upvalues[index].clone()
+                        };
+                        upvalues.push(upvalue);
+                    }
+                    let closure = self.heap.alloc(Closure::new(function, upvalues));
+                    self.push(Value::Closure(closure));
+                },
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                },
+                OpCode::Return => {
+                    let value = self.pop();
+                    let frame = self.frames.pop().expect("frame must be running");
+                    self.close_upvalues(frame.base);
+                    self.stack.truncate(frame.base);
+                    if self.frames.is_empty() {
+                        return Ok(());
+                    }
+                    self.push(value);
+                },
+                OpCode::Class => {
+                    let name = self.read_name();
+                    let class = self.heap.alloc_cell(Class::new(name));
+                    self.push(Value::Class(class));
+                },
+                OpCode::Inherit => {
+                    let Value::Class(superclass) = self.peek(1) else {
+                        return Err(RuntimeError::InvalidSuperclass {
+                            snippet: self.snippet_at(ip),
+                            found: self.peek(1).to_string(),
+                        });
+                    };
+                    let Value::Class(subclass) = self.peek(0) else {
+                        return Err(self.corrupt(ip, "inheriting into a non-class"));
+                    };
+
+                    let methods = superclass.borrow().methods.clone();
+                    subclass.borrow_mut().methods.extend(methods);
+                    self.pop();
+                },
+                OpCode::Method => {
+                    let name = self.read_name();
+                    let Value::Closure(method) = self.pop() else {
+                        return Err(self.corrupt(ip, "method not a closure"));
+                    };
+                    let Value::Class(class) = self.peek(0) else {
+                        return Err(self.corrupt(ip, "method added to a non-class"));
+                    };
+                    class.borrow_mut().methods.insert(name, method);
+                },
+            }
+        }
+    }
+
+    /// Calls the callee below `argument_count` arguments on the top of the stack.
+    fn call_value(&mut self, ip: usize, argument_count: usize) -> Result<(), RuntimeError> {
+        let base = self.stack.len() - argument_count - 1;
+        let callee = self.stack[base].clone();
+        let arity = match &callee {
+            Value::Closure(closure) => closure.function.arity,
+            Value::BoundMethod(bound) => bound.method.function.arity,
+            Value::Class(class) => class.borrow().methods.get("init").map_or(0, |init| init.function.arity),
+            _ => {
+                return Err(RuntimeError::NotCallable {
+                    snippet: self.snippet_at(ip),
+                    found: callee.type_name(),
+                });
+            },
+        };
+
+        if argument_count != arity {
+            return Err(RuntimeError::ArityMismatch {
+                snippet: self.snippet_at(ip),
+                expected: arity,
+                found: argument_count,
+            });
+        }
+
+        // The frame of the top-level script doesn't count as a call.
+        if self.frames.len() - 1 == MAX_CALL_DEPTH {
+            return Err(RuntimeError::StackOverflow {
+                snippet: self.snippet_at(ip),
+            });
+        }
+
+        match callee {
+            Value::Closure(closure) => self.call_closure(closure, base),
+            Value::BoundMethod(bound) => {
+                self.stack[base] = bound.receiver.clone();
+                self.call_closure(bound.method.clone(), base);
+            },
+            Value::Class(class) => {
+                let init = class.borrow().methods.get("init").cloned();
+                self.stack[base] = Value::Instance(self.heap.alloc_cell(Instance::new(class)));
+                if let Some(init) = init {
+                    self.call_closure(init, base);
+                }
+            },
+            _ => unreachable!("callee must have been checked"),
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn call_closure(&mut self, closure: Rc<Closure>, base: usize) {
+        self.frames.push(CallFrame { closure, ip: 0, base });
+    }
+
+    /// Looks a method up in `class`, and binds it to `receiver`.
+    fn bind_method(
+        &mut self,
+        ip: usize,
+        class: &Rc<RefCell<Class>>,
+        name: &Rc<str>,
+        receiver: Value,
+    ) -> Result<Value, RuntimeError> {
+        let method = class.borrow().methods.get(name).cloned();
+        match method {
+            Some(method) => Ok(Value::BoundMethod(self.heap.alloc(BoundMethod { receiver, method }))),
+            None => Err(RuntimeError::UndefinedProperty {
+                snippet: self.snippet_at(ip),
+                name: name.to_string(),
+            }),
+        }
+    }
+
+    /// Returns the captured variable living in the stack `slot`, which is shared by every closure capturing it.
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let open = self.open_upvalues
+            .iter()
+            .find(|u| matches!(*u.borrow(), Upvalue::Open(s) if s == slot));
+        if let Some(upvalue) = open {
+            return upvalue.clone();
+        }
+
+        let upvalue = self.heap.alloc_cell(Upvalue::Open(slot));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+
+    /// Moves the captured variables living in the stack slots from `start` into the heap.
+    fn close_upvalues(&mut self, start: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let mut upvalue = upvalue.borrow_mut();
+            match *upvalue {
+                Upvalue::Open(slot) if slot >= start => {
+                    *upvalue = Upvalue::Closed(stack[slot].clone());
+                    false
+                },
+                _ => true,
+            }
+        });
+    }
+
+    fn binary_number_op(
+        &mut self,
+        ip: usize,
+        operator: &str,
+        op: fn(Number, Number) -> Value,
+    ) -> Result<(), RuntimeError> {
+        let right = self.pop();
+        let left = self.pop();
+        match (&left, &right) {
+            (Value::Number(a), Value::Number(b)) => {
+                self.push(op(*a, *b));
+                Ok(())
+            },
+            _ => Err(RuntimeError::NumberOperands {
+                snippet: self.snippet_at(ip),
+                operator: operator.to_string(),
+                left: left.type_name(),
+                right: right.type_name(),
+            }),
+        }
+    }
+
+    /// Returns the snippet of the source which the instruction at `ip` in the current frame is compiled from.
+    fn snippet_at(&self, ip: usize) -> Snippet {
+        Snippet::new(self.frame().closure.function.chunk.span_at(ip))
+    }
+
+    fn undefined_variable(&self, ip: usize, name: &str) -> RuntimeError {
+        RuntimeError::UndefinedVariable {
+            snippet: self.snippet_at(ip),
+            name: name.to_string(),
+        }
+    }
+
+    /// Returns the error of a value of the wrong type, which the compiler never leaves on the stack,
+    /// but which a compiled Lox file may, since `loxc::verify` doesn't trace the types of values.
+    fn corrupt(&self, ip: usize, reason: &str) -> RuntimeError {
+        RuntimeError::CorruptBytecode {
+            snippet: self.snippet_at(ip),
+            reason: reason.to_string(),
+        }
+    }
+
+    fn not_instance(&self, ip: usize, object: &Value) -> RuntimeError {
+        RuntimeError::NotInstance {
+            snippet: self.snippet_at(ip),
+            found: object.type_name(),
+        }
+    }
+
+    #[inline(always)]
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("frame must be running")
+    }
+
+    #[inline(always)]
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("frame must be running")
+    }
+
+    #[inline(always)]
+    fn read_u8(&mut self) -> u8 {
+        let frame = self.frame_mut();
+        let byte = frame.closure.function.chunk.code()[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    #[inline(always)]
+    fn read_u16(&mut self) -> u16 {
+        u16::from_be_bytes([self.read_u8(), self.read_u8()])
+    }
+
+    #[inline(always)]
+    fn read_constant(&mut self) -> Constant {
+        let index = self.read_u16() as usize;
+        self.frame().closure.function.chunk.constants()[index].clone()
+    }
+
+    #[inline(always)]
+    fn read_name(&mut self) -> Rc<str> {
+        match self.read_constant() {
+            Constant::String(name) => name,
+            constant => unreachable!("name must be a string constant, found {constant:?}"),
+        }
+    }
+
+    #[inline(always)]
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    #[inline(always)]
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack must not underflow")
+    }
+
+    #[inline(always)]
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::*;
+    use crate::ast::Stmt;
+    use crate::interpreter::Interpreter;
+    use crate::parser::Parser;
+    use crate::report::{Renderer, ToDiagnostic};
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+    use crate::src::{ResolveSnippet, SourceMap};
+    use crate::vm::compiler::Compiler;
+    use crate::vm::loxc::Loxc;
+
+    /// A shared buffer to capture the output of `print` statements.
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Output {
+        fn take(&self) -> String {
+            String::from_utf8(self.0.take()).unwrap()
+        }
+    }
+
+    fn compile(source: &str) -> (Vec<Stmt>, Rc<Function>) {
+        let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+        let stmts = Parser::new(&tokens).parse().unwrap();
+        Resolver::new().resolve(&stmts).unwrap();
+        let function = Compiler::new().compile(&stmts).unwrap();
+        // Every program is run from its compiled Lox file, which checks that the file passes verification.
+        let loxc = Loxc::from_bytes(&Loxc::new(source.as_bytes(), function).to_bytes()).unwrap();
+        (stmts, loxc.function)
+    }
+
+    /// Runs `source` on both the VM and the tree-walker, and returns the result of the VM
+    /// after checking that the tree-walker, and the VM collecting garbage on every allocation, behave the same.
+    fn interpret(source: &str) -> Result<String, RuntimeError> {
+        let (stmts, function) = compile(source);
+        let run = |gc| {
+            let output = Output::default();
+            Vm::with_gc(output.clone(), gc).interpret(function.clone()).map(|_| output.take())
+        };
+        let result = run(GcConfig::default());
+        assert_eq!(run(GcConfig { stress: true, ..GcConfig::default() }), result, "{source} under GC stress");
+
+        let output = Output::default();
+        let expected = Interpreter::with_output(output.clone()).interpret(&stmts).map(|_| output.take());
+        assert_eq!(result, expected, "{source}");
+        result
+    }
+
+    #[test]
+    fn vm_interpret() {
+        let cases = [
+            ("print 1 + 2 * 3;", "7\n"),
+            ("print (1 + 2) * 3 / 2;", "4.5\n"),
+            ("print -(1 - 3);", "2\n"),
+            ("print 'ab' + \"cd\";", "abcd\n"),
+            ("var a = 1; print 'a${a}b${a + 1 > 1}c${nil}${'d'}'; print \"${a}\";", "a1btruecnild\n1\n"),
+            ("class A {} fun f() {} print '${A} ${A()} ${f}';", "A A instance <fn f>\n"),
+            ("print 1 < 2; print 2 <= 1; print 3 > 3; print 3 >= 3;", "true\nfalse\nfalse\ntrue\n"),
+            ("var n = 0 / 0; print n < n; print n >= n; print n == n; print n != n;", "false\nfalse\nfalse\ntrue\n"),
+            ("print !nil; print !0; print !'';", "true\nfalse\nfalse\n"),
+            ("print nil == nil; print nil == false; print 1 == 1; print 'a' != 'a';", "true\nfalse\ntrue\nfalse\n"),
+            ("print nil or 'a'; print 0 or 'b'; print nil and 'c'; print 1 and 'd';", "a\n0\nnil\nd\n"),
+            ("if (nil) print 1; else print 2; if (0) print 3;", "2\n3\n"),
+            ("var a; print a; var a = 1; print a; a = a + 1; print a;", "nil\n1\n2\n"),
+            ("var a = 1; var b; print b = a = 3; print a;", "3\n3\n"),
+            ("var a = 'g'; { var a = 'l'; print a; a = 'l2'; print a; } print a;", "l\nl2\ng\n"),
+            ("for (var i = 0; i < 3; i = i + 1) { var j = i * 2; print j; }", "0\n2\n4\n"),
+            ("fun f(a, b) { print a + b; } print f; print f(1, 2);", "<fn f>\n3\nnil\n"),
+            ("fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } print fib(15);", "610\n"),
+            ("{ fun f(n) { if (n > 0) return f(n - 1); return 'done'; } print f(3); }", "done\n"),
+            (r#"
+                fun counter() {
+                    var i = 0;
+                    fun count() { i = i + 1; return i; }
+                    return count;
+                }
+                var a = counter(); var b = counter();
+                print a(); print a(); print b();
+            "#, "1\n2\n1\n"),
+            (r#"
+                var get; var set;
+                fun outer() {
+                    var x = 'before';
+                    fun middle() {
+                        fun g() { return x; }
+                        fun s(v) { x = v; }
+                        get = g; set = s;
+                    }
+                    middle();
+                    print get();
+                    set('after');
+                    print x;
+                }
+                outer();
+                print get();
+            "#, "before\nafter\nafter\n"),
+            (r#"
+                var fs = nil;
+                for (var i = 0; i < 2; i = i + 1) {
+                    var j = i;
+                    fun f() { return i + j; }
+                    if (fs == nil) fs = f;
+                }
+                print fs();
+            "#, "2\n"),
+            (r#"
+                var a = 'global';
+                {
+                    fun show() { print a; }
+                    show();
+                    var a = 'block';
+                    show();
+                }
+            "#, "global\nglobal\n"),
+            ("class A { m() { return 'm'; } } print A; var a = A(); print a; print a.m;", "A\nA instance\n<fn m>\n"),
+            ("class A {} var a = A(); a.f = 1; a.f = a.f + 1; print a.f; print a == a; print A() == a;", "2\ntrue\nfalse\n"),
+            (r#"
+                class Point {
+                    init(x, y) { this.x = x; this.y = y; }
+                    sum() { return this.x + this.y; }
+                }
+                var p = Point(1, 2);
+                var sum = p.sum;
+                p.x = 10;
+                print sum();
+                print p.init(3, 4) == p;
+                print p.sum();
+            "#, "12\ntrue\n7\n"),
+            ("class A { init() { this.a = 1; return; this.a = 2; } } print A().a;", "1\n"),
+            ("class A { m() { return 1; } } var a = A(); fun f() { return 2; } a.m = f; print a.m();", "2\n"),
+            (r#"
+                class A {
+                    init(name) { this.name = name; }
+                    hello() { return 'hello ' + this.name; }
+                    who() { return 'A'; }
+                }
+                class B < A {
+                    init(name) { super.init(name + '!'); }
+                    who() { var f = super.who; fun g() { return 'B < ' + f(); } return g(); }
+                }
+                class C < B {}
+                var c = C('c');
+                print c.hello(); print c.who();
+            "#, "hello c!\nB < A\n"),
+        ];
+
+        for (source, expected) in cases {
+            assert_eq!(interpret(source).unwrap(), expected, "{source}");
+        }
+    }
+
+    #[test]
+    fn vm_interpret_error() {
+        let cases = [
+            "print -'abc';",
+            "print clock;",
+            "print 1 < nil;",
+            "print 1 >= 'a';",
+            "print 'a' + 1;",
+            "{ var a = 1; } print a;",
+            "var a; { b = a; }",
+            "'f'(1);",
+            "fun f(a) {} f(1, 2);",
+            "fun f() { f(); } f();",
+            "class A {} fun f() { A(); f(); } f();",
+            "class A {} A().b;",
+            "var a = 1; a.b = 2;",
+            "var a = 1; print a.b;",
+            "fun A() {} class B < A {}",
+            "class A { init(a) {} } A();",
+            "class A {} A(1);",
+            "class A {} class B < A { m() { return super.m(); } } B().m();",
+        ];
+
+        for source in cases {
+            assert!(interpret(source).is_err(), "{source}");
+        }
+    }
+
+    #[test]
+    fn vm_interpret_error_rendered() {
+        let source = "class A {}\nfun f() {\n  return A().nope;\n}\nprint f();\n";
+        let mut sources = SourceMap::new();
+        sources.add("test.lox", source.as_bytes());
+        let render = |error: RuntimeError| {
+            let mut diagnostic = error.to_diagnostic();
+            diagnostic.resolve_snippet(&sources);
+            Renderer::Plain.render(&[diagnostic])
+        };
+
+        let (stmts, function) = compile(source);
+        let vm = render(Vm::with_output(Output::default()).interpret(function).unwrap_err());
+        let tree = render(Interpreter::with_output(Output::default()).interpret(&stmts).unwrap_err());
+        assert_eq!(vm, tree);
+        assert!(vm.contains("A().nope") && vm.contains("^^^^"), "{vm}");
+    }
+
+    #[test]
+    fn vm_interpret_after_error() {
+        let run = |vm: &mut Vm, source: &str| vm.interpret(compile(source).1);
+        let output = Output::default();
+        let mut vm = Vm::with_gc(output.clone(), GcConfig { stress: true, ..GcConfig::default() });
+        let source = "var f; { var a = 'captured'; fun g() { return a; } f = g; nil(); }";
+        assert!(run(&mut vm, source).is_err());
+        run(&mut vm, "var b = 'b'; print f();").unwrap();
+        assert_eq!(output.take(), "captured\n");
+    }
+
+    #[test]
+    fn vm_interpret_collect() {
+        let source = r#"
+            class Node {
+                init() { this.me = this; }
+                closure() { fun f() { return this; } this.f = f; }
+            }
+            fun counter() {
+                var i = 0;
+                fun count() { i = i + 1; return i; }
+                return count;
+            }
+            for (var i = 0; i < 10; i = i + 1) {
+                Node().closure();
+                counter();
+            }
+            var node = Node();
+        "#;
+        let mut vm = Vm::with_output(Output::default());
+        vm.interpret(compile(source).1).unwrap();
+        // `Node` with its 2 methods, `counter`, and `node`.
+        assert_eq!(vm.heap.collect(), 5);
+    }
+}
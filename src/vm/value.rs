@@ -0,0 +1,238 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+use crate::gc::{Trace, Tracer};
+use crate::types::Number;
+use crate::vm::chunk::Chunk;
+
+/// A value of the VM, which behaves the same as `crate::value::Value` of the tree-walker.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(Number),
+    String(Rc<str>),
+    Closure(Rc<Closure>),
+    BoundMethod(Rc<BoundMethod>),
+    Class(Rc<RefCell<Class>>),
+    Instance(Rc<RefCell<Instance>>),
+}
+
+impl Value {
+    /// Returns whether the value counts as true in a condition: `nil` and `false` are falsey,
+    /// and everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Closure(_) | Value::BoundMethod(_) | Value::Class(_) => "callable",
+            Value::Instance(_) => "instance",
+        }
+    }
+}
+
+/// Values of different types are never equal, and callables and instances are equal
+/// only to themselves.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Closure(a), Value::Closure(b)) => Rc::ptr_eq(a, b),
+            (Value::BoundMethod(a), Value::BoundMethod(b)) => Rc::ptr_eq(a, b),
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Trace for Value {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Value::Closure(closure) => tracer.visit(closure),
+            Value::BoundMethod(bound) => tracer.visit(bound),
+            Value::Class(class) => tracer.visit(class),
+            Value::Instance(instance) => tracer.visit(instance),
+            Value::Nil | Value::Bool(_) | Value::Number(_) | Value::String(_) => {},
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => f.write_str("nil"),
+            Value::Bool(b) => b.fmt(f),
+            Value::Number(n) => n.fmt(f),
+            Value::String(s) => f.write_str(s),
+            Value::Closure(c) => c.function.fmt(f),
+            Value::BoundMethod(b) => b.method.function.fmt(f),
+            Value::Class(c) => f.write_str(&c.borrow().name),
+            Value::Instance(i) => write!(f, "{} instance", i.borrow().class.borrow().name),
+        }
+    }
+}
+
+/// A compiled function, which is the prototype of the closures created from it at runtime.
+#[derive(Debug)]
+pub struct Function {
+    /// The name of the function, which is empty for the top-level script.
+    pub name: Rc<str>,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.name.is_empty() {
+            f.write_str("<script>")
+        } else {
+            write!(f, "<fn {}>", self.name)
+        }
+    }
+}
+
+/// A variable captured by a closure, which lives on the stack while it's in scope,
+/// and is moved into the heap once it goes out of scope.
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+impl Trace for Upvalue {
+    fn trace(&self, tracer: &mut Tracer) {
+        if let Upvalue::Closed(value) = self {
+            value.trace(tracer);
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = Upvalue::Closed(Value::Nil);
+    }
+}
+
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+impl Closure {
+    pub fn new(function: Rc<Function>, upvalues: Vec<Rc<RefCell<Upvalue>>>) -> Self {
+        Closure { function, upvalues }
+    }
+}
+
+impl Trace for Closure {
+    fn trace(&self, tracer: &mut Tracer) {
+        for upvalue in &self.upvalues {
+            tracer.visit(upvalue);
+        }
+    }
+}
+
+/// Doesn't print the upvalues, which may contain the closure itself.
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Closure")
+            .field("name", &self.function.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A method together with the instance it's accessed from, which is bound to `this`.
+#[derive(Debug)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Rc<Closure>,
+}
+
+impl Trace for BoundMethod {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.receiver.trace(tracer);
+        tracer.visit(&self.method);
+    }
+}
+
+pub struct Class {
+    pub name: Rc<str>,
+    /// The methods of the class, including the ones inherited from its superclasses.
+    pub methods: HashMap<Rc<str>, Rc<Closure>>,
+}
+
+impl Class {
+    pub fn new(name: Rc<str>) -> Self {
+        Class {
+            name,
+            methods: HashMap::new(),
+        }
+    }
+}
+
+impl Trace for Class {
+    fn trace(&self, tracer: &mut Tracer) {
+        for method in self.methods.values() {
+            tracer.visit(method);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.methods.clear();
+    }
+}
+
+/// Doesn't print the methods, which may refer to the class itself.
+impl fmt::Debug for Class {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Class")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+pub struct Instance {
+    pub class: Rc<RefCell<Class>>,
+    pub fields: HashMap<Rc<str>, Value>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<RefCell<Class>>) -> Self {
+        Instance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+}
+
+impl Trace for Instance {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.visit(&self.class);
+        for value in self.fields.values() {
+            value.trace(tracer);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.fields.clear();
+    }
+}
+
+/// Doesn't print the fields, which may contain the instance itself.
+impl fmt::Debug for Instance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Instance")
+            .field("class", &self.class.borrow().name)
+            .finish_non_exhaustive()
+    }
+}